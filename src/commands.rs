@@ -1,10 +1,21 @@
 use anyhow::Result;
 use clap::Subcommand;
 
+pub mod args;
+pub mod diff;
+pub mod history;
+pub mod import_ludusavi;
+pub mod link;
 pub mod list;
+pub mod promote;
+pub mod register;
+pub mod restore;
 pub mod set_repository;
+pub mod set_retention;
 pub mod show;
+pub mod status;
 pub mod sync;
+pub mod unlink;
 
 #[derive(Subcommand, Debug)]
 pub enum Commands {
@@ -14,8 +25,28 @@ pub enum Commands {
     List,
     #[command(about = "Show the definition of a registered game")]
     Show(show::ShowArgs),
+    #[command(about = "Add a new game to the repository")]
+    Register(register::RegisterArgs),
+    #[command(about = "Add a game to the repository from a Ludusavi manifest")]
+    ImportLudusavi(import_ludusavi::ImportLudusaviArgs),
+    #[command(about = "Activate save sync for a game on this device")]
+    Link(link::LinkArgs),
+    #[command(about = "Deactivate save sync for a game on this device")]
+    Unlink(unlink::UnlinkArgs),
+    #[command(about = "Show whether linked games are in sync without changing anything")]
+    Status(status::StatusArgs),
     #[command(about = "Synchronize linked games")]
     Sync(sync::SyncArgs),
+    #[command(about = "List the save files that differ between this device and the repository")]
+    Diff(diff::DiffArgs),
+    #[command(about = "List the saves of a game stored in the repository")]
+    History(history::HistoryArgs),
+    #[command(about = "Set how many old saves are kept in the repository")]
+    SetRetention(set_retention::SetRetentionArgs),
+    #[command(about = "Roll the save on this device back to an earlier snapshot")]
+    Restore(restore::RestoreArgs),
+    #[command(about = "Make a save kept from a conflict the current save")]
+    Promote(promote::PromoteArgs),
 }
 
 pub fn exec(command: &Commands) -> Result<()> {
@@ -23,6 +54,16 @@ pub fn exec(command: &Commands) -> Result<()> {
         Commands::SetRepository(args) => set_repository::set_repository(args),
         Commands::List => list::list(),
         Commands::Show(args) => show::show(args),
+        Commands::Register(args) => register::register(args),
+        Commands::ImportLudusavi(args) => import_ludusavi::import_ludusavi(args),
+        Commands::Link(args) => link::link(args),
+        Commands::Unlink(args) => unlink::unlink(args),
+        Commands::Status(args) => status::status(args),
         Commands::Sync(args) => sync::sync(args),
+        Commands::Diff(args) => diff::diff(args),
+        Commands::History(args) => history::history(args),
+        Commands::SetRetention(args) => set_retention::set_retention(args),
+        Commands::Restore(args) => restore::restore(args),
+        Commands::Promote(args) => promote::promote(args),
     }
 }
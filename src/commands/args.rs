@@ -0,0 +1,76 @@
+use clap::{Args, ValueEnum};
+
+use gamesavesync::{
+    games::{ConflictPolicy, GamePlatform},
+    sync::SyncOptions,
+};
+
+// The library types are kept free of clap, so the command line has its own copies
+
+#[derive(Clone, Copy, ValueEnum, Debug)]
+pub enum PlatformArg {
+    Linux,
+    Windows,
+}
+
+impl From<PlatformArg> for GamePlatform {
+    fn from(platform: PlatformArg) -> Self {
+        match platform {
+            PlatformArg::Linux => GamePlatform::Linux,
+            PlatformArg::Windows => GamePlatform::Windows,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum, Debug)]
+pub enum ConflictPolicyArg {
+    Ask,
+    Skip,
+    PreferLocal,
+    PreferRepository,
+    PreferNewest,
+    Fail,
+}
+
+impl From<ConflictPolicyArg> for ConflictPolicy {
+    fn from(policy: ConflictPolicyArg) -> Self {
+        match policy {
+            ConflictPolicyArg::Ask => ConflictPolicy::Ask,
+            ConflictPolicyArg::Skip => ConflictPolicy::Skip,
+            ConflictPolicyArg::PreferLocal => ConflictPolicy::PreferLocal,
+            ConflictPolicyArg::PreferRepository => ConflictPolicy::PreferRepository,
+            ConflictPolicyArg::PreferNewest => ConflictPolicy::PreferNewest,
+            ConflictPolicyArg::Fail => ConflictPolicy::Fail,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct SyncOptionsArgs {
+    #[arg(short, long, help = "Simulate without modifying files")]
+    dry_run: bool,
+    #[arg(
+        long,
+        value_enum,
+        help = "How to resolve conflicts, overriding the game default"
+    )]
+    on_conflict: Option<ConflictPolicyArg>,
+    #[arg(
+        long,
+        help = "Seconds to wait for another device to finish syncing [default: 30]"
+    )]
+    lock_timeout: Option<u64>,
+    #[arg(long, help = "Remove a lock left behind by another device")]
+    break_lock: bool,
+}
+
+impl From<&SyncOptionsArgs> for SyncOptions {
+    fn from(args: &SyncOptionsArgs) -> Self {
+        SyncOptions {
+            dry_run: args.dry_run,
+            on_conflict: args.on_conflict.map(ConflictPolicy::from),
+            lock_timeout: args.lock_timeout,
+            break_lock: args.break_lock,
+        }
+    }
+}
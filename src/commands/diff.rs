@@ -0,0 +1,139 @@
+use anyhow::{Context, Error, Result};
+use clap::Args;
+use uuid::Uuid;
+
+use gamesavesync::{
+    games::{
+        definition::{definition_exists, load_definition},
+        history::read_snapshot_manifest,
+    },
+    repository::{Repository, get_repository},
+    sync::{
+        diff::{FileChange, FileDiff, SaveComparison, diff_files, diff_save_state},
+        files::{ResolvedSaveFiles, get_manifest_files},
+        load_save_state,
+    },
+    utils::config,
+};
+
+#[derive(Args, Debug)]
+pub struct DiffArgs {
+    #[arg(help = "The game to compare")]
+    game: String,
+    #[arg(
+        long,
+        requires = "to",
+        help = "Compare two snapshots instead, starting from this one"
+    )]
+    from: Option<Uuid>,
+    #[arg(long, requires = "from", help = "The snapshot to compare against")]
+    to: Option<Uuid>,
+}
+
+pub fn diff(args: &DiffArgs) -> Result<()> {
+    let config = config::load().with_context(|| "failed to load config")?;
+    let repository = get_repository(&config.repository)?;
+    if !definition_exists(&repository, &args.game)? {
+        return Err(Error::msg(format!("game {} is not defined", args.game)));
+    }
+    if let (Some(from), Some(to)) = (args.from, args.to) {
+        let from_files = snapshot_files(&repository, &args.game, from)?;
+        let to_files = snapshot_files(&repository, &args.game, to)?;
+        print_diff(
+            &format!("Changes from {from} to {to}"),
+            diff_files(&from_files, &to_files),
+        );
+        return Ok(());
+    }
+    if !config.linked_games.contains(&args.game) {
+        return Err(Error::msg(format!(
+            "game {} is not linked on this device",
+            args.game
+        )));
+    }
+    let definition = load_definition(&repository, &args.game)?;
+    let state = load_save_state(&definition, &repository)?;
+    let repository_files = match &state.repository {
+        Some((files, _)) => files,
+        None => {
+            println!("{} has no save in the repository", args.game);
+            return Ok(());
+        }
+    };
+    print_changes(
+        &state.local_files,
+        state.synced.as_ref().map(|(files, _)| files),
+        repository_files,
+    );
+    Ok(())
+}
+
+fn snapshot_files(repository: &impl Repository, game: &str, id: Uuid) -> Result<ResolvedSaveFiles> {
+    let snapshot = read_snapshot_manifest(repository, game, id)?.ok_or(Error::msg(format!(
+        "snapshot {id} does not exist for {game}"
+    )))?;
+    get_manifest_files(&snapshot, &snapshot.definition)
+}
+
+pub fn print_changes(
+    local_files: &ResolvedSaveFiles,
+    synced_files: Option<&ResolvedSaveFiles>,
+    repository_files: &ResolvedSaveFiles,
+) {
+    for diff in diff_save_state(local_files, synced_files, repository_files) {
+        let title = match diff.comparison {
+            SaveComparison::LocalSinceSync => "Changed here since the last sync",
+            SaveComparison::RepositorySinceSync => "Changed in the repository since the last sync",
+            SaveComparison::LocalToRepository => "Local save compared to the repository",
+        };
+        print_diff(title, diff.files);
+    }
+}
+
+fn format_size_delta(old: u64, new: u64) -> String {
+    if new >= old {
+        format!("+{} bytes", new - old)
+    } else {
+        format!("-{} bytes", old - new)
+    }
+}
+
+fn format_time_delta(delta: time::Duration) -> String {
+    let seconds = delta.whole_seconds().unsigned_abs();
+    let amount = match seconds {
+        0..60 => format!("{seconds} seconds"),
+        60..3600 => format!("{} minutes", seconds / 60),
+        3600..86400 => format!("{} hours", seconds / 3600),
+        _ => format!("{} days", seconds / 86400),
+    };
+    if delta.is_negative() {
+        format!("{amount} older")
+    } else {
+        format!("{amount} newer")
+    }
+}
+
+fn print_diff(title: &str, diffs: Vec<FileDiff>) {
+    if diffs.is_empty() {
+        println!("{}: nothing", title);
+        return;
+    }
+    println!("{}:", title);
+    for diff in diffs {
+        match (diff.change, &diff.old, &diff.new) {
+            (FileChange::Added, _, Some(new)) => {
+                println!("\t+ {} ({} bytes)", diff.name, new.size)
+            }
+            (FileChange::Removed, Some(old), _) => {
+                println!("\t- {} ({} bytes)", diff.name, old.size)
+            }
+            (FileChange::Modified, Some(old), Some(new)) => println!(
+                "\t~ {} ({}, {})",
+                diff.name,
+                format_size_delta(old.size, new.size),
+                format_time_delta(new.modified - old.modified)
+            ),
+            _ => unreachable!("file diffs have the metadata of their changed sides"),
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use anyhow::{Context, Error, Result};
+use clap::Args;
+
+use gamesavesync::{
+    games::{
+        definition::definition_exists, history::list_snapshots, manifest::read_repository_manifest,
+    },
+    repository::get_repository,
+    utils::config,
+};
+
+#[derive(Args, Debug)]
+pub struct HistoryArgs {
+    #[arg(help = "The game to show the history of")]
+    game: String,
+}
+
+pub fn history(args: &HistoryArgs) -> Result<()> {
+    let config = config::load().with_context(|| "failed to load config")?;
+    let repository = get_repository(&config.repository)?;
+    if !definition_exists(&repository, &args.game)? {
+        return Err(Error::msg(format!("game {} is not defined", args.game)));
+    }
+    let snapshots = list_snapshots(&repository, &args.game)?;
+    if snapshots.is_empty() {
+        println!("{} has no saves in the repository", args.game);
+        return Ok(());
+    }
+    let head = read_repository_manifest(&repository, &args.game)?.map(|manifest| manifest.id);
+    for snapshot in snapshots {
+        let offset = match time::UtcOffset::current_local_offset() {
+            Ok(local_offset) => local_offset,
+            Err(_) => snapshot.timestamp.offset(),
+        };
+        println!(
+            "{} {} from {}{}",
+            snapshot.id,
+            snapshot.timestamp.to_offset(offset),
+            snapshot.device.as_deref().unwrap_or("unknown device"),
+            match &snapshot.branch {
+                _ if head == Some(snapshot.id) => " (current)".into(),
+                Some(branch) => format!(" (branch {branch})"),
+                None => "".into(),
+            }
+        );
+    }
+    Ok(())
+}
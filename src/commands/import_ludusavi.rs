@@ -0,0 +1,120 @@
+use std::io::IsTerminal;
+use std::path::PathBuf;
+
+use anyhow::{Context, Error, Result};
+use clap::Args;
+
+use gamesavesync::{
+    games::{
+        ConflictPolicy, GamePlatform,
+        definition::GameDefinition,
+        ludusavi::{
+            LudusaviManifest, convert_game, default_platform, find_game, load_ludusavi_manifest,
+            search_titles,
+        },
+    },
+    repository::get_repository,
+    utils::{config, paths::make_path_safe},
+};
+
+use crate::commands::{
+    args::{ConflictPolicyArg, PlatformArg},
+    link::set_wine_prefix,
+    register::{add_definition, validate_game_name},
+};
+
+#[derive(Args, Debug)]
+pub struct ImportLudusaviArgs {
+    #[arg(help = "The downloaded Ludusavi manifest.yaml")]
+    manifest: PathBuf,
+    #[arg(help = "The title to search for, prompts for it if not given")]
+    title: Option<String>,
+    #[arg(long, help = "The name to register the game as, defaults to its title")]
+    name: Option<String>,
+    #[arg(long, value_enum, help = "The platform the game runs on")]
+    platform: Option<PlatformArg>,
+    #[arg(
+        long,
+        help = "The Wine prefix of a Windows game on this device, found from the Steam app id if not given"
+    )]
+    wine_prefix: Option<String>,
+    #[arg(long, value_enum, help = "How to resolve sync conflicts by default")]
+    on_conflict: Option<ConflictPolicyArg>,
+    #[arg(long, help = "Do not link the game on this device")]
+    no_link: bool,
+}
+
+pub fn import_ludusavi(args: &ImportLudusaviArgs) -> Result<()> {
+    let mut config = config::load().with_context(|| "failed to load config")?;
+    let repository = get_repository(&config.repository)?;
+    let manifest = load_ludusavi_manifest(&args.manifest)?;
+    let title = select_title(&manifest, args.title.as_deref())?;
+    let name = match &args.name {
+        Some(name) => name.clone(),
+        None => make_path_safe(&title),
+    };
+    validate_game_name(&repository, &name)?;
+    set_wine_prefix(&name, args.wine_prefix.as_deref(), &mut config)?;
+    let definition = GameDefinition {
+        conflict_policy: args.on_conflict.map(ConflictPolicy::from),
+        ..convert_definition(
+            &manifest,
+            &title,
+            &name,
+            args.platform.map(GamePlatform::from),
+        )?
+    };
+    add_definition(&definition, &mut config, &repository, args.no_link)
+}
+
+pub fn select_title(manifest: &LudusaviManifest, query: Option<&str>) -> Result<String> {
+    let titles = match query {
+        Some(query) => search_titles(manifest, query),
+        None => manifest.keys().map(String::as_str).collect(),
+    };
+    match titles.as_slice() {
+        [] => Err(Error::msg(format!(
+            "no game matching {} in the Ludusavi manifest",
+            query.unwrap_or_default()
+        ))),
+        [title] => Ok(title.to_string()),
+        _ if std::io::stdin().is_terminal() => Ok(inquire::Select::new("Game", titles)
+            .prompt()
+            .with_context(|| "failed to prompt for game")?
+            .to_string()),
+        _ => Err(Error::msg(format!(
+            "several games match {}: {}",
+            query.unwrap_or_default(),
+            titles.join(", ")
+        ))),
+    }
+}
+
+pub fn convert_definition(
+    manifest: &LudusaviManifest,
+    title: &str,
+    name: &str,
+    platform: Option<GamePlatform>,
+) -> Result<GameDefinition> {
+    let (title, game) = find_game(manifest, title).ok_or(Error::msg(format!(
+        "{title} is not in the Ludusavi manifest"
+    )))?;
+    let platform = platform.unwrap_or_else(|| default_platform(game));
+    println!(
+        "Importing {} for {} from the Ludusavi manifest",
+        title, platform
+    );
+    let import = convert_game(name, game, platform);
+    for rule in &import.unsupported {
+        println!("- Skipped {}", rule);
+    }
+    if import.definition.paths.is_empty() {
+        return Err(Error::msg(format!(
+            "no supported save paths for {title} on {platform}"
+        )));
+    }
+    for path in &import.definition.paths {
+        println!("- Save path {}", path.path);
+    }
+    Ok(import.definition)
+}
@@ -0,0 +1,59 @@
+use anyhow::{Context, Error, Result};
+use clap::Args;
+
+use gamesavesync::{
+    games::definition::definition_exists,
+    repository::{Repository, get_repository},
+    sync::{SyncOptions, sync_games},
+    utils::config::{self, Config},
+};
+
+use crate::commands::sync::{CliConflictResolver, CliObserver};
+
+#[derive(Args, Debug)]
+pub struct LinkArgs {
+    #[arg(help = "The game to link")]
+    game: String,
+    #[arg(
+        long,
+        help = "The Wine prefix of a Windows game on this device, found from the Steam app id if not given"
+    )]
+    wine_prefix: Option<String>,
+}
+
+pub fn link(args: &LinkArgs) -> Result<()> {
+    let mut config = config::load().with_context(|| "failed to load config")?;
+    let repository = get_repository(&config.repository)?;
+    set_wine_prefix(&args.game, args.wine_prefix.as_deref(), &mut config)?;
+    link_game(&args.game, &mut config, &repository)
+}
+
+pub fn set_wine_prefix(game: &str, wine_prefix: Option<&str>, config: &mut Config) -> Result<()> {
+    if let Some(wine_prefix) = wine_prefix {
+        config.wine_prefixes.insert(game.into(), wine_prefix.into());
+        config::save(config).with_context(|| "failed to save config")?;
+    }
+    Ok(())
+}
+
+pub fn link_game(game: &str, config: &mut Config, repository: &impl Repository) -> Result<()> {
+    if !definition_exists(repository, game)? {
+        return Err(Error::msg(format!("game {game} is not defined")));
+    }
+    if config.linked_games.insert(game.into()) {
+        config::save(config).with_context(|| "failed to save config")?;
+        println!("Linked {}", game);
+    } else {
+        println!("{} is already linked", game);
+    }
+    let options = SyncOptions::default();
+    sync_games(
+        repository,
+        config,
+        Some(game),
+        &options,
+        &mut CliConflictResolver,
+        &mut CliObserver::new(&options),
+    )?;
+    Ok(())
+}
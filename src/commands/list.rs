@@ -1,6 +1,8 @@
 use anyhow::{Context, Result};
 
-use crate::{games::definition::list_definitions, repository::get_repository, utils::config};
+use gamesavesync::{
+    games::definition::list_definitions, repository::get_repository, utils::config,
+};
 
 pub fn list() -> Result<()> {
     let config = config::load().with_context(|| "failed to load config")?;
@@ -0,0 +1,93 @@
+use anyhow::{Context, Error, Result};
+use clap::Args;
+
+use gamesavesync::{
+    games::{
+        blobs::collect_garbage,
+        history::{find_branch, remove_snapshot},
+        manifest::{
+            GameSaveManifest, ManifestChange, read_repository_manifest, write_repository_manifest,
+        },
+    },
+    repository::{Repository, get_repository},
+    sync::{SyncOptions, lock_game},
+    utils::{config, device::device_name},
+};
+
+use crate::commands::sync::CliObserver;
+
+#[derive(Args, Debug)]
+pub struct PromoteArgs {
+    #[arg(help = "The game to promote a branch for")]
+    game: String,
+    #[arg(help = "The branch to make the current save, as listed by history")]
+    branch: String,
+    #[arg(long, help = "Delete the branch instead of making it the current save")]
+    discard: bool,
+}
+
+pub fn promote(args: &PromoteArgs) -> Result<()> {
+    let config = config::load().with_context(|| "failed to load config")?;
+    let repository = get_repository(&config.repository)?;
+    repository.pull()?;
+    let options = SyncOptions::default();
+    let lock = lock_game(
+        &args.game,
+        &repository,
+        &options,
+        &mut CliObserver::new(&options),
+    )?;
+    let snapshot = find_branch(&repository, &args.game, &args.branch)?.ok_or(Error::msg(
+        format!("branch {} does not exist for {}", args.branch, args.game),
+    ))?;
+    if args.discard
+        && read_repository_manifest(&repository, &args.game)?
+            .is_some_and(|head| head.id == snapshot.id)
+    {
+        return Err(Error::msg(format!(
+            "branch {} is the current save for {} and can't be discarded",
+            args.branch, args.game
+        )));
+    }
+    match &lock {
+        Some(lock) => promote_snapshot(args, snapshot, lock)?,
+        None => promote_snapshot(args, snapshot, &repository)?,
+    }
+    drop(lock);
+    repository.push()?;
+    if args.discard {
+        println!("Discarded branch {} for {}", args.branch, args.game);
+    } else {
+        println!(
+            "Promoted {} to the current save for {}, it will be applied on the next sync",
+            args.branch, args.game
+        );
+    }
+    Ok(())
+}
+
+fn promote_snapshot(
+    args: &PromoteArgs,
+    snapshot: GameSaveManifest,
+    repository: &impl Repository,
+) -> Result<()> {
+    if args.discard {
+        remove_snapshot(repository, &args.game, snapshot.id)?;
+        collect_garbage(repository, &args.game)?;
+        repository.commit(&format!(
+            "Discard {} branch {} from {}",
+            args.game,
+            args.branch,
+            device_name()
+        ))
+    } else {
+        write_repository_manifest(
+            &GameSaveManifest {
+                branch: None,
+                ..snapshot
+            },
+            ManifestChange::Promoted,
+            repository,
+        )
+    }
+}
@@ -0,0 +1,219 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Error, Result};
+use clap::Args;
+use inquire::validator::Validation;
+
+use gamesavesync::{
+    games::{
+        ConflictPolicy, GAME_PLATFORMS, GamePlatform,
+        definition::{
+            GameDefinition, GameDefinitionPath, check_game_name, definition_exists,
+            write_definition,
+        },
+        ludusavi::load_ludusavi_manifest,
+        paths::rewrite_path,
+    },
+    repository::{Repository, get_repository},
+    utils::config::{self, Config},
+};
+
+use crate::commands::{
+    args::{ConflictPolicyArg, PlatformArg},
+    import_ludusavi::{convert_definition, select_title},
+    link::{link_game, set_wine_prefix},
+};
+
+#[derive(Args, Debug)]
+pub struct RegisterArgs {
+    #[arg(help = "The name of the game to register")]
+    game: String,
+    #[arg(long, value_enum, help = "The platform the game runs on")]
+    platform: Option<PlatformArg>,
+    #[arg(
+        long = "path",
+        help = "A save file or folder, prompts for the definition if not given"
+    )]
+    paths: Vec<String>,
+    #[arg(
+        long,
+        value_name = "GLOB",
+        requires = "paths",
+        help = "Only sync files in the save paths matching the glob"
+    )]
+    include: Vec<String>,
+    #[arg(
+        long,
+        value_name = "GLOB",
+        requires = "paths",
+        help = "Never sync files in the save paths matching the glob"
+    )]
+    exclude: Vec<String>,
+    #[arg(
+        long,
+        value_name = "MANIFEST",
+        conflicts_with = "paths",
+        help = "Find the save paths in a downloaded Ludusavi manifest.yaml"
+    )]
+    from_ludusavi: Option<PathBuf>,
+    #[arg(long, help = "The Steam app id of the game")]
+    steam_app_id: Option<u64>,
+    #[arg(
+        long,
+        help = "The Wine prefix of a Windows game on this device, found from the Steam app id if not given"
+    )]
+    wine_prefix: Option<String>,
+    #[arg(long, value_enum, help = "How to resolve sync conflicts by default")]
+    on_conflict: Option<ConflictPolicyArg>,
+    #[arg(long, help = "Do not link the game on this device")]
+    no_link: bool,
+}
+
+pub fn register(args: &RegisterArgs) -> Result<()> {
+    let mut config = config::load().with_context(|| "failed to load config")?;
+    let repository = get_repository(&config.repository)?;
+    validate_game_name(&repository, &args.game)?;
+    set_wine_prefix(&args.game, args.wine_prefix.as_deref(), &mut config)?;
+    let definition = if let Some(manifest) = &args.from_ludusavi {
+        let manifest = load_ludusavi_manifest(manifest)?;
+        let title = select_title(&manifest, Some(&args.game))?;
+        let definition = convert_definition(
+            &manifest,
+            &title,
+            &args.game,
+            args.platform.map(GamePlatform::from),
+        )?;
+        GameDefinition {
+            steam_app_id: args.steam_app_id.or(definition.steam_app_id),
+            conflict_policy: args.on_conflict.map(ConflictPolicy::from),
+            ..definition
+        }
+    } else if args.paths.is_empty() {
+        prompt_definition(args)?
+    } else {
+        let definition = GameDefinition {
+            name: args.game.clone(),
+            platform: args
+                .platform
+                .map_or(GamePlatform::Linux, GamePlatform::from),
+            paths: args
+                .paths
+                .iter()
+                .map(|path| GameDefinitionPath {
+                    path: path.clone(),
+                    include: args.include.clone(),
+                    exclude: args.exclude.clone(),
+                })
+                .collect(),
+            steam_app_id: args.steam_app_id,
+            conflict_policy: args.on_conflict.map(ConflictPolicy::from),
+        };
+        for path in &definition.paths {
+            rewrite_path(&path.path, &definition)
+                .with_context(|| format!("invalid save path {}", path.path))?;
+            path.filter()?;
+        }
+        definition
+    };
+    add_definition(&definition, &mut config, &repository, args.no_link)
+}
+
+pub fn validate_game_name(repository: &impl Repository, game: &str) -> Result<()> {
+    check_game_name(game)?;
+    if definition_exists(repository, game)? {
+        return Err(Error::msg(format!("game {} is already defined", game)));
+    }
+    Ok(())
+}
+
+pub fn add_definition(
+    definition: &GameDefinition,
+    config: &mut Config,
+    repository: &impl Repository,
+    no_link: bool,
+) -> Result<()> {
+    write_definition(repository, definition)?;
+    println!("Registered {}", definition.name);
+    if !no_link {
+        link_game(&definition.name, config, repository)?;
+    }
+    repository.push()
+}
+
+fn prompt_definition(args: &RegisterArgs) -> Result<GameDefinition> {
+    let platform = match args.platform.map(GamePlatform::from) {
+        Some(platform) => platform,
+        None => inquire::Select::new("Platform", GAME_PLATFORMS.to_vec())
+            .prompt()
+            .with_context(|| "failed to prompt for platform")?,
+    };
+    let steam_app_id = match args.steam_app_id {
+        Some(steam_app_id) => Some(steam_app_id),
+        None => inquire::CustomType::<u64>::new("Steam app id")
+            .with_help_message("Press escape to skip")
+            .prompt_skippable()
+            .with_context(|| "failed to prompt for Steam app id")?,
+    };
+    let mut definition = GameDefinition {
+        name: args.game.clone(),
+        platform,
+        paths: vec![],
+        steam_app_id,
+        conflict_policy: args.on_conflict.map(ConflictPolicy::from),
+    };
+    loop {
+        let path = inquire::Text::new("Save path")
+            .with_help_message("Leave empty when all save paths have been added")
+            .with_validator(|input: &str| {
+                if input.is_empty() {
+                    return Ok(Validation::Valid);
+                }
+                Ok(match rewrite_path(input, &definition) {
+                    Ok(_) => Validation::Valid,
+                    Err(error) => Validation::Invalid(error.into()),
+                })
+            })
+            .prompt()
+            .with_context(|| "failed to prompt for save path")?;
+        if path.is_empty() {
+            break;
+        }
+        let include = prompt_globs("Include globs", "include every file")?;
+        let exclude = prompt_globs("Exclude globs", "exclude nothing")?;
+        definition.paths.push(GameDefinitionPath {
+            path,
+            include,
+            exclude,
+        });
+    }
+    Ok(definition)
+}
+
+fn prompt_globs(message: &str, default: &str) -> Result<Vec<String>> {
+    let globs = inquire::Text::new(message)
+        .with_help_message(&format!(
+            "Separate globs with commas, leave empty to {default}"
+        ))
+        .with_validator(|input: &str| {
+            let path = GameDefinitionPath {
+                include: split_globs(input),
+                ..GameDefinitionPath::new(String::new())
+            };
+            Ok(match path.filter() {
+                Ok(_) => Validation::Valid,
+                Err(error) => Validation::Invalid(format!("{error:#}").into()),
+            })
+        })
+        .prompt()
+        .with_context(|| format!("failed to prompt for {}", message.to_lowercase()))?;
+    Ok(split_globs(&globs))
+}
+
+fn split_globs(input: &str) -> Vec<String> {
+    input
+        .split(',')
+        .map(str::trim)
+        .filter(|glob| !glob.is_empty())
+        .map(String::from)
+        .collect()
+}
@@ -0,0 +1,89 @@
+use anyhow::{Context, Error, Result};
+use clap::Args;
+use uuid::Uuid;
+
+use gamesavesync::{
+    games::{history::read_snapshot_manifest, journal::rollback_apply},
+    repository::{Repository, get_repository},
+    sync::{SyncOptions, backup_local_files, files::get_local_files, lock_game, restore_snapshot},
+    utils::config,
+};
+
+use crate::commands::{args::SyncOptionsArgs, sync::CliObserver};
+
+#[derive(Args, Debug)]
+pub struct RestoreArgs {
+    #[arg(help = "The game to restore")]
+    game: String,
+    #[arg(long, help = "The snapshot to restore, as listed by history")]
+    snapshot: Uuid,
+    #[arg(
+        long,
+        help = "Also make the snapshot the current save in the repository"
+    )]
+    make_current: bool,
+    #[command(flatten)]
+    options: SyncOptionsArgs,
+}
+
+pub fn restore(args: &RestoreArgs) -> Result<()> {
+    let config = config::load().with_context(|| "failed to load config")?;
+    let repository = get_repository(&config.repository)?;
+    let options = SyncOptions::from(&args.options);
+    if !config.linked_games.contains(&args.game) {
+        return Err(Error::msg(format!(
+            "game {} is not linked on this device",
+            args.game
+        )));
+    }
+    if !options.dry_run {
+        repository.pull()?;
+    }
+    let snapshot = read_snapshot_manifest(&repository, &args.game, args.snapshot)?.ok_or(
+        Error::msg(format!(
+            "snapshot {} does not exist for {}",
+            args.snapshot, args.game
+        )),
+    )?;
+    println!("Restoring {} to {}", args.game, snapshot.id);
+    let mut observer = CliObserver::new(&options);
+    let lock = lock_game(&args.game, &repository, &options, &mut observer)?;
+    if !options.dry_run && rollback_apply(&args.game)? {
+        println!("- Rolled back an interrupted sync");
+    }
+    let local_files = get_local_files(&snapshot.definition, None)?;
+    if options.dry_run {
+        println!("- Backing up {} local files", local_files.len());
+    } else if let Some(backup_dir) = backup_local_files(&args.game, &local_files)? {
+        println!("- Backed up local save to {}", backup_dir.display());
+    }
+    if args.make_current && !options.dry_run {
+        println!(
+            "- Making {} the current save in the repository",
+            snapshot.id
+        );
+    }
+    match &lock {
+        Some(lock) => restore_snapshot(
+            &snapshot,
+            &local_files,
+            args.make_current,
+            lock,
+            &options,
+            &mut observer,
+        )?,
+        None => restore_snapshot(
+            &snapshot,
+            &local_files,
+            args.make_current,
+            &repository,
+            &options,
+            &mut observer,
+        )?,
+    }
+    if options.dry_run {
+        return Ok(());
+    }
+    drop(lock);
+    repository.push()
+}
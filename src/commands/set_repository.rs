@@ -1,24 +1,211 @@
-use std::{env::current_dir, path::PathBuf};
+use std::io::IsTerminal;
+use std::{
+    env::current_dir,
+    path::{Path, PathBuf},
+};
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Error, Result};
 use clap::Args;
 
-use crate::{repository, utils::config};
+use gamesavesync::{
+    repository::{
+        self, AnyRepositoryConfig,
+        archive_repository::{self, ArchiveFormat, ArchiveRepositoryConfig},
+        git_repository,
+        local_repository::LocalRepositoryConfig,
+        s3_repository::S3RepositoryConfig,
+        sftp_repository::SftpRepositoryConfig,
+        webdav_repository::{self, WebDavRepositoryConfig},
+    },
+    utils::config,
+};
 
 #[derive(Args, Debug)]
 pub struct SetRepositoryArgs {
-    #[arg(help = "The path to the repository")]
-    path: PathBuf,
+    #[arg(
+        help = "The path to the repository or to a .zip or .tar archive, the http(s) URL of a WebDAV folder, an s3://bucket/prefix location, or an sftp://user@host/path location"
+    )]
+    location: String,
+    #[arg(long, help = "The user to log in to the server as")]
+    username: Option<String>,
+    #[arg(
+        long,
+        requires = "username",
+        help = "The password to check the server with, prompts for it if not given. It is not saved, set GAMESAVESYNC_WEBDAV_PASSWORD for other commands"
+    )]
+    password: Option<String>,
+    #[arg(long, help = "A PEM certificate to trust for the server")]
+    ca_certificate: Option<PathBuf>,
+    #[arg(long, help = "Do not check the server certificate")]
+    accept_invalid_certificates: bool,
+    #[arg(long, help = "The http(s) URL of the S3-compatible service")]
+    endpoint: Option<String>,
+    #[arg(
+        long,
+        default_value = "us-east-1",
+        help = "The region of the S3 bucket"
+    )]
+    region: String,
+    #[arg(
+        long,
+        help = "The S3 access key, uses AWS_ACCESS_KEY_ID when running if not given"
+    )]
+    access_key_id: Option<String>,
+    #[arg(
+        long,
+        requires = "access_key_id",
+        help = "The S3 secret key to check the bucket with, prompts for it if not given. It is not saved, set AWS_SECRET_ACCESS_KEY for other commands"
+    )]
+    secret_access_key: Option<String>,
+    #[arg(
+        long,
+        help = "The private key to log in to the SFTP server with, uses the SSH agent if not given"
+    )]
+    key_file: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "The known_hosts file to check the SFTP server against, uses ~/.ssh/known_hosts if not given"
+    )]
+    known_hosts: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Keep the repository path as a git working tree with a commit for every change"
+    )]
+    git: bool,
+    #[arg(
+        long,
+        help = "A git remote to pull from and push to when syncing, cloned if the path is empty"
+    )]
+    git_remote: Option<String>,
+}
+
+// Secrets are only used to check the repository now, later commands read them from the environment
+fn prompt_secret(
+    name: &str,
+    variable: &str,
+    user: &Option<String>,
+    secret: &Option<String>,
+) -> Result<Option<String>> {
+    if std::env::var_os(variable).is_some() {
+        return Ok(secret.clone());
+    }
+    if user.is_some() {
+        println!(
+            "The {} is not saved, set {variable} to use the repository",
+            name.to_lowercase()
+        );
+    }
+    if secret.is_some() || user.is_none() || !std::io::stdin().is_terminal() {
+        return Ok(secret.clone());
+    }
+    let secret = inquire::Password::new(name)
+        .without_confirmation()
+        .prompt()
+        .with_context(|| format!("failed to prompt for {}", name.to_lowercase()))?;
+    Ok(Some(secret))
+}
+
+fn sftp_config(
+    location: &str,
+    args: &SetRepositoryArgs,
+    working_dir: &Path,
+) -> Result<SftpRepositoryConfig> {
+    let (authority, path) = location.split_once('/').unwrap_or((location, ""));
+    let (user, address) = match authority.rsplit_once('@') {
+        Some((user, address)) => (Some(user.to_string()), address),
+        None => (args.username.clone(), authority),
+    };
+    let user = user.ok_or(Error::msg(
+        "a user is needed for SFTP repositories, as sftp://user@host/path or --username",
+    ))?;
+    let (host, port) = match address.rsplit_once(':') {
+        Some((host, port)) => (
+            host,
+            port.parse()
+                .with_context(|| format!("{port} is not a valid port"))?,
+        ),
+        None => (address, 22),
+    };
+    // Paths under ~/ are relative to the login directory, anything else is absolute
+    let path = match path.strip_prefix('~') {
+        Some(path) => path.trim_start_matches('/').to_string(),
+        None => format!("/{path}"),
+    };
+    Ok(SftpRepositoryConfig {
+        host: host.into(),
+        port,
+        user,
+        path,
+        key_file: args.key_file.as_ref().map(|path| working_dir.join(path)),
+        known_hosts: args.known_hosts.as_ref().map(|path| working_dir.join(path)),
+    })
+}
+
+fn repository_config(args: &SetRepositoryArgs) -> Result<AnyRepositoryConfig> {
+    let working_dir = current_dir().with_context(|| "failed to get working directory")?;
+    if args.location.starts_with("http://") || args.location.starts_with("https://") {
+        return Ok(AnyRepositoryConfig::WebDav(WebDavRepositoryConfig {
+            url: args.location.clone(),
+            username: args.username.clone(),
+            password: prompt_secret(
+                "Password",
+                webdav_repository::PASSWORD_VARIABLE,
+                &args.username,
+                &args.password,
+            )?,
+            ca_certificate: args
+                .ca_certificate
+                .as_ref()
+                .map(|path| working_dir.join(path)),
+            accept_invalid_certificates: args.accept_invalid_certificates,
+        }));
+    }
+    if let Some(location) = args.location.strip_prefix("s3://") {
+        let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+        let endpoint = args
+            .endpoint
+            .clone()
+            .ok_or(Error::msg("an --endpoint is needed for S3 repositories"))?;
+        return Ok(AnyRepositoryConfig::S3(S3RepositoryConfig {
+            endpoint,
+            bucket: bucket.into(),
+            region: args.region.clone(),
+            prefix: prefix.trim_matches('/').into(),
+            access_key_id: args.access_key_id.clone(),
+            secret_access_key: prompt_secret(
+                "Secret access key",
+                "AWS_SECRET_ACCESS_KEY",
+                &args.access_key_id,
+                &args.secret_access_key,
+            )?,
+        }));
+    }
+    if let Some(location) = args.location.strip_prefix("sftp://") {
+        return Ok(AnyRepositoryConfig::Sftp(sftp_config(
+            location,
+            args,
+            &working_dir,
+        )?));
+    }
+    if args.git || args.git_remote.is_some() {
+        return Ok(AnyRepositoryConfig::Git(git_repository::init_repository(
+            &working_dir.join(&args.location),
+            args.git_remote.as_deref(),
+        )?));
+    }
+    let path = working_dir.join(&args.location);
+    if ArchiveFormat::from_path(&path).is_some() {
+        archive_repository::create_archive(&path)?;
+        return Ok(AnyRepositoryConfig::Archive(ArchiveRepositoryConfig {
+            path,
+        }));
+    }
+    Ok(AnyRepositoryConfig::Local(LocalRepositoryConfig { path }))
 }
 
 pub fn set_repository(args: &SetRepositoryArgs) -> Result<()> {
-    let path = current_dir()
-        .with_context(|| "failed to get working directory")?
-        .join(args.path.clone());
     let mut config = config::load().with_context(|| "failed to load config")?;
-    let new_repository = repository::AnyRepositoryConfig::Local(
-        repository::local_repository::LocalRepositoryConfig { path },
-    );
+    let new_repository = repository_config(args)?;
     match config.repository {
         Some(ref repository) => {
             println!(
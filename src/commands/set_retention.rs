@@ -0,0 +1,36 @@
+use anyhow::{Context, Result};
+use clap::Args;
+
+use gamesavesync::{
+    repository::{
+        Repository, get_repository,
+        settings::{RetentionPolicy, read_settings, write_settings},
+    },
+    utils::config,
+};
+
+#[derive(Args, Debug)]
+pub struct SetRetentionArgs {
+    #[arg(long, help = "Keep the most recent snapshots")]
+    keep_last: Option<usize>,
+    #[arg(long, help = "Keep the last snapshot of each of the most recent days")]
+    keep_daily: Option<usize>,
+    #[arg(long, help = "Keep the last snapshot of each of the most recent weeks")]
+    keep_weekly: Option<usize>,
+}
+
+pub fn set_retention(args: &SetRetentionArgs) -> Result<()> {
+    let config = config::load().with_context(|| "failed to load config")?;
+    let repository = get_repository(&config.repository)?;
+    let mut settings = read_settings(&repository)?;
+    settings.retention = RetentionPolicy {
+        keep_last: args.keep_last,
+        keep_daily: args.keep_daily,
+        keep_weekly: args.keep_weekly,
+    };
+    if args.keep_last.is_none() && args.keep_daily.is_none() && args.keep_weekly.is_none() {
+        println!("Keeping all snapshots");
+    }
+    write_settings(&repository, &settings)?;
+    repository.push()
+}
@@ -1,7 +1,7 @@
 use anyhow::{Context, Result};
 use clap::Args;
 
-use crate::{
+use gamesavesync::{
     games::{definition, paths},
     repository::get_repository,
     utils::config,
@@ -19,16 +19,31 @@ pub fn show(args: &ShowArgs) -> Result<()> {
     let definition = definition::load_definition(&repository, &args.game)?;
     println!("Name: {}", &definition.name);
     println!("Platform: {}", &definition.platform);
+    if let Some(conflict_policy) = definition.conflict_policy {
+        println!("On Conflict: {}", conflict_policy);
+    }
+    if let Some(steam_app_id) = definition.steam_app_id {
+        println!("Steam App Id: {}", steam_app_id);
+    }
+    if let Some(wine_prefix) = config.wine_prefixes.get(&definition.name) {
+        println!("Wine Prefix: {}", wine_prefix);
+    }
     if definition.paths.is_empty() {
         println!("Save Paths: None");
     } else {
         println!("Save Paths:");
-        for path in definition.paths {
+        for path in &definition.paths {
             println!("\t{}", path.path);
-            match paths::rewrite_path(&path.path) {
+            match paths::rewrite_path(&path.path, &definition) {
                 Ok(rewritten) => println!("\t\t➙ {}", rewritten.display()),
                 Err(error) => println!("\t\t× {}", error),
             }
+            if !path.include.is_empty() {
+                println!("\t\tIncluding {}", path.include.join(", "));
+            }
+            if !path.exclude.is_empty() {
+                println!("\t\tExcluding {}", path.exclude.join(", "));
+            }
         }
     }
     Ok(())
@@ -0,0 +1,104 @@
+use anyhow::{Context, Error, Result};
+use clap::Args;
+use time::{OffsetDateTime, macros::format_description};
+
+use gamesavesync::{
+    games::definition::definition_exists,
+    repository::get_repository,
+    sync::status::{GameStatusEntry, game_status},
+    utils::config,
+};
+
+#[derive(Args, Debug)]
+pub struct StatusArgs {
+    #[arg(help = "Show only a specific game")]
+    game: Option<String>,
+    #[arg(long, help = "Print the status as JSON for scripts")]
+    json: bool,
+}
+
+pub fn status(args: &StatusArgs) -> Result<()> {
+    let config = config::load().with_context(|| "failed to load config")?;
+    let repository = get_repository(&config.repository)?;
+    let games: Vec<&String> = match &args.game {
+        Some(game) if !config.linked_games.contains(game) => {
+            return Err(Error::msg(format!(
+                "game {game} is not linked on this device"
+            )));
+        }
+        Some(game) => vec![game],
+        None => config.linked_games.iter().collect(),
+    };
+    let mut entries = vec![];
+    for game in games {
+        if !definition_exists(&repository, game)? {
+            eprintln!(
+                "Skipping {}, it is no longer defined in the repository",
+                game
+            );
+            continue;
+        }
+        entries.push(game_status(game, &repository)?);
+    }
+    if args.json {
+        let serialized =
+            serde_json::to_string_pretty(&entries).with_context(|| "failed to serialize status")?;
+        println!("{}", serialized);
+    } else if entries.is_empty() {
+        println!("No games are linked on this device");
+    } else {
+        print_table(&entries)?;
+    }
+    Ok(())
+}
+
+fn format_timestamp(timestamp: Option<OffsetDateTime>) -> Result<String> {
+    let timestamp = match timestamp {
+        Some(timestamp) => timestamp,
+        None => return Ok("-".into()),
+    };
+    let offset = match time::UtcOffset::current_local_offset() {
+        Ok(local_offset) => local_offset,
+        Err(_) => timestamp.offset(),
+    };
+    let format = format_description!("[year]-[month]-[day] [hour]:[minute]");
+    Ok(timestamp.to_offset(offset).format(format)?)
+}
+
+fn print_table(entries: &[GameStatusEntry]) -> Result<()> {
+    let mut rows = vec![[
+        "Game".to_string(),
+        "State".to_string(),
+        "Synced Here".to_string(),
+        "Repository Updated".to_string(),
+        "By".to_string(),
+    ]];
+    for entry in entries {
+        rows.push([
+            entry.game.clone(),
+            entry.status.to_string(),
+            format_timestamp(entry.synced_here)?,
+            format_timestamp(entry.repository_updated)?,
+            match (&entry.repository_device, entry.repository_updated) {
+                (Some(device), _) => device.clone(),
+                (None, Some(_)) => "unknown device".into(),
+                (None, None) => "-".into(),
+            },
+        ]);
+    }
+    let mut widths = [0; 5];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for row in rows {
+        let line: Vec<_> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    }
+    Ok(())
+}
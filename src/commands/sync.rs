@@ -1,124 +1,186 @@
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::io::IsTerminal;
 
-use anyhow::{Context, Error, Result};
+use anyhow::{Context, Result};
 use clap::Args;
-use relative_path::{PathExt, RelativePath, RelativePathBuf};
-use time::{OffsetDateTime, UtcDateTime};
-use uuid::Uuid;
+use indicatif::{ProgressBar, ProgressStyle};
+use time::UtcOffset;
 
-use crate::{
-    games::{
-        definition::{GameDefinition, list_definitions, load_definition},
-        manifest::{
-            GameSaveFileMetadata, GameSaveManifest, read_repository_manifest, read_synced_manifest,
-            write_repository_manifest, write_synced_manifest,
+use gamesavesync::{
+    games::ConflictPolicy,
+    repository::get_repository,
+    sync::{
+        SyncOptions,
+        callbacks::{
+            Conflict, ConflictResolution, ConflictResolver, SaveSide, SyncEvent, SyncObserver,
+            TransferDirection,
         },
-        paths::{self, rewrite_path},
+        sync_games,
     },
-    repository::{Repository, get_repository},
-    utils::{config, paths::make_path_safe},
+    utils::config,
 };
 
+use crate::commands::{args::SyncOptionsArgs, diff::print_changes};
+
 #[derive(Args, Debug)]
 pub struct SyncArgs {
     #[arg(help = "Sync only a specific game")]
     game: Option<String>,
-    #[arg(short, long, help = "Simulate without modifying files")]
-    dry_run: bool,
+    #[command(flatten)]
+    options: SyncOptionsArgs,
 }
 
 pub fn sync(args: &SyncArgs) -> Result<()> {
     let config = config::load().with_context(|| "failed to load config")?;
     let repository = get_repository(&config.repository)?;
-    match &args.game {
-        Some(game) => sync_game(&game, &repository, args)?,
-        None => {
-            for game in list_definitions(&repository)? {
-                sync_game(&game, &repository, &args)?
-            }
-        }
+    let options = SyncOptions::from(&args.options);
+    if args.game.is_none() && config.linked_games.is_empty() {
+        println!("No games are linked on this device");
     }
+    sync_games(
+        &repository,
+        &config,
+        args.game.as_deref(),
+        &options,
+        &mut CliConflictResolver,
+        &mut CliObserver::new(&options),
+    )?;
     Ok(())
 }
 
-#[derive(Debug)]
-enum SyncDirection {
-    ToRepository,
-    FromRepository,
-    DoNothing,
+pub struct CliObserver {
+    dry_run: bool,
+    progress: Option<ProgressBar>,
 }
 
-fn sync_game(game: &str, repository: &impl Repository, args: &SyncArgs) -> Result<()> {
-    println!("Checking {}", game);
-    let definition = load_definition(repository, game)?;
-    let local_files = get_local_files(&definition)?;
-    let repository_state = match read_repository_manifest(repository, game)? {
-        Some(manifest) => Some((get_manifest_files(&manifest)?, manifest)),
-        None => None,
-    };
-    let synced_state = match read_synced_manifest(game)? {
-        Some(manifest) => Some((get_manifest_files(&manifest)?, manifest)),
-        None => None,
-    };
-    let sync_direction = match &repository_state {
-        Some((repository_files, repository_manifest)) => match &synced_state {
-            Some((synced_files, synced_manifest)) => {
-                let local_changed = !save_files_equal(&local_files, &synced_files);
-                let repository_changed = !save_files_equal(&synced_files, &repository_files);
-                match (local_changed, repository_changed) {
-                    (true, true) => {
-                        let local_last_mod = local_files.values().map(|file| file.2.modified).max();
-                        conflict_prompt(
-                            &format!(
-                                "{} has changed here and in the repository (last synced at {})",
-                                &game, &synced_manifest.timestamp
-                            ),
-                            local_last_mod,
-                            repository_manifest.timestamp,
-                        )?
-                    }
-                    (true, false) => SyncDirection::ToRepository,
-                    (false, true) => SyncDirection::FromRepository,
-                    (false, false) => SyncDirection::DoNothing,
-                }
+impl CliObserver {
+    pub fn new(options: &SyncOptions) -> Self {
+        CliObserver {
+            dry_run: options.dry_run,
+            progress: None,
+        }
+    }
+
+    fn start_progress(&mut self, bytes: u64) {
+        let progress = ProgressBar::new(bytes);
+        if let Ok(style) =
+            ProgressStyle::with_template("  [{bar:30}] {bytes}/{total_bytes} {wide_msg}")
+        {
+            progress.set_style(style.progress_chars("=> "));
+        }
+        self.progress = Some(progress);
+    }
+
+    fn finish_progress(&mut self) {
+        if let Some(progress) = self.progress.take() {
+            progress.finish_and_clear();
+        }
+    }
+}
+
+impl SyncObserver for CliObserver {
+    fn on_event(&mut self, event: &SyncEvent) {
+        match event {
+            SyncEvent::Transferring { bytes, .. } if !self.dry_run && *bytes > 0 => {
+                self.finish_progress();
+                self.start_progress(*bytes);
+                return;
             }
-            // No local manifest
-            None => {
-                let local_last_mod = local_files.values().map(|file| file.2.modified).max();
-                conflict_prompt(
-                    &format!("{} has not been synced to this device", &game),
-                    local_last_mod,
-                    repository_manifest.timestamp,
-                )?
+            SyncEvent::FileCopied { path, bytes, .. } if !self.dry_run => {
+                if let Some(progress) = &self.progress {
+                    progress.set_message(path.display().to_string());
+                    progress.inc(*bytes);
+                }
+                return;
             }
-        },
-        // No repository manifest
-        None => SyncDirection::ToRepository,
-    };
-    match sync_direction {
-        SyncDirection::ToRepository => {
-            println!("- Storing save in repository");
-            sync_game_to_repository(&definition, &local_files, repository, args)?;
+            _ => self.finish_progress(),
         }
-        SyncDirection::FromRepository => {
-            println!("- Applying save from repository");
-            match repository_state {
-                Some((files, manifest)) => {
-                    sync_game_from_repository(&manifest, &files, repository, args)?
+        match event {
+            SyncEvent::Checking { game } => println!("Checking {}", game),
+            SyncEvent::GameNotDefined { game } => println!(
+                "Skipping {}, it is no longer defined in the repository",
+                game
+            ),
+            SyncEvent::LockWaiting { holder, .. } => {
+                println!("- Waiting for sync on {} to finish", holder)
+            }
+            SyncEvent::LockBroken { holder, .. } => {
+                println!("- Breaking lock held by {}", holder)
+            }
+            SyncEvent::RolledBack { .. } => println!("- Rolled back an interrupted sync"),
+            SyncEvent::ConflictResolved {
+                conflict,
+                policy,
+                resolution,
+            } => {
+                let kept = match (policy, resolution) {
+                    (_, ConflictResolution::DoNothing) => "skipping",
+                    (ConflictPolicy::PreferNewest, ConflictResolution::UseLocal { .. }) => {
+                        "keeping newer local device save"
+                    }
+                    (ConflictPolicy::PreferNewest, ConflictResolution::UseRepository { .. }) => {
+                        "keeping newer repository save"
+                    }
+                    (_, ConflictResolution::UseLocal { .. }) => "keeping local device save",
+                    (_, ConflictResolution::UseRepository { .. }) => "keeping repository save",
+                };
+                println!("- {}, {}", conflict, kept);
+            }
+            SyncEvent::Branching { side, branch, .. } => match side {
+                SaveSide::Local => println!("- Keeping local device save as branch {}", branch),
+                SaveSide::Repository => {
+                    println!("- Keeping repository save as branch {}", branch)
                 }
-                None => {
-                    unreachable!("impossible to sync from repository with no repository manifest")
+            },
+            SyncEvent::Storing { .. } => println!("- Storing save in repository"),
+            SyncEvent::Applying { .. } => println!("- Applying save from repository"),
+            SyncEvent::Merging { .. } => {
+                println!("- Merging changes from here and the repository")
+            }
+            SyncEvent::FileCopied {
+                path, direction, ..
+            } => match direction {
+                TransferDirection::ToRepository => {
+                    println!("- Storing {} in repository", path.display())
+                }
+                TransferDirection::FromRepository => {
+                    println!("- Applying {} from repository", path.display())
                 }
+            },
+            SyncEvent::FileRemoved { path, .. } if self.dry_run => {
+                println!("- Removing save file at {}", path.display())
+            }
+            SyncEvent::SnapshotPruned { id, .. } => println!("- Pruned snapshot {}", id),
+            SyncEvent::Transferring { .. }
+            | SyncEvent::FileRemoved { .. }
+            | SyncEvent::Finished { .. } => {}
+        }
+    }
+}
+
+pub struct CliConflictResolver;
+
+impl ConflictResolver for CliConflictResolver {
+    fn resolve(&mut self, conflict: &Conflict) -> Result<ConflictResolution> {
+        if !std::io::stdin().is_terminal() {
+            println!("- {}, skipping since there is no terminal to ask", conflict);
+            return Ok(ConflictResolution::DoNothing);
+        }
+        loop {
+            match conflict_prompt(conflict)? {
+                Some(resolution) => return Ok(resolution),
+                None => print_changes(
+                    conflict.local_files,
+                    conflict.synced_files,
+                    conflict.repository_files,
+                ),
             }
         }
-        SyncDirection::DoNothing => {}
     }
-    Ok(())
 }
 
 struct ConflictChoice {
-    sync_direction: SyncDirection,
+    // No resolution shows the differences and asks again
+    resolution: Option<ConflictResolution>,
     label: String,
 }
 
@@ -128,212 +190,52 @@ impl std::fmt::Display for ConflictChoice {
     }
 }
 
-fn conflict_prompt(
-    message: &str,
-    local_last_mod: Option<UtcDateTime>,
-    repository_synced: OffsetDateTime,
-) -> Result<SyncDirection> {
-    let offset = match time::UtcOffset::current_local_offset() {
+fn conflict_prompt(conflict: &Conflict) -> Result<Option<ConflictResolution>> {
+    let offset = match UtcOffset::current_local_offset() {
         Ok(local_offset) => local_offset,
-        Err(_) => repository_synced.offset(),
+        Err(_) => conflict.repository_synced.offset(),
     };
+    let local_label = format!(
+        "local device save{}",
+        match conflict.local_modified() {
+            Some(local_last_mod) => format!(" (modified {})", local_last_mod.to_offset(offset)),
+            None => "".into(),
+        }
+    );
+    let repository_label = format!(
+        "repository save (synced {})",
+        conflict.repository_synced.to_offset(offset)
+    );
     let choice = inquire::Select::new(
-        message,
+        &conflict.to_string(),
         vec![
             ConflictChoice {
-                sync_direction: SyncDirection::DoNothing,
-                label: format!("Do nothing"),
+                resolution: Some(ConflictResolution::DoNothing),
+                label: "Do nothing".into(),
             },
             ConflictChoice {
-                sync_direction: SyncDirection::ToRepository,
-                label: format!(
-                    "Keep local device save{}",
-                    match local_last_mod {
-                        Some(local_last_mod) =>
-                            format!(" (modified {})", local_last_mod.to_offset(offset)),
-                        None => "".into(),
-                    }
-                ),
+                resolution: Some(ConflictResolution::UseLocal { keep_other: true }),
+                label: format!("Use {local_label}, keep the other as a branch"),
             },
             ConflictChoice {
-                sync_direction: SyncDirection::FromRepository,
-                label: format!(
-                    "Keep repository save (synced {})",
-                    repository_synced.to_offset(offset)
-                ),
+                resolution: Some(ConflictResolution::UseRepository { keep_other: true }),
+                label: format!("Use {repository_label}, keep the other as a branch"),
+            },
+            ConflictChoice {
+                resolution: Some(ConflictResolution::UseLocal { keep_other: false }),
+                label: format!("Keep {local_label}"),
+            },
+            ConflictChoice {
+                resolution: Some(ConflictResolution::UseRepository { keep_other: false }),
+                label: format!("Keep {repository_label}"),
+            },
+            ConflictChoice {
+                resolution: None,
+                label: "Show what changed".into(),
             },
         ],
     )
     .prompt()
     .with_context(|| "failed to prompt for sync direction")?;
-    Ok(choice.sync_direction)
-}
-
-fn sync_game_to_repository(
-    definition: &GameDefinition,
-    local_files: &ResolvedSaveFiles,
-    repository: &impl Repository,
-    args: &SyncArgs,
-) -> Result<()> {
-    let manifest_id = Uuid::new_v4();
-    let mut files = HashMap::new();
-    for (real_path, (path, file, metadata)) in local_files {
-        if args.dry_run {
-            println!("- Storing {} in repository", real_path.display());
-        } else {
-            let repository_path = RelativePath::new(&definition.name)
-                .join(manifest_id.to_string())
-                .join(make_path_safe(path))
-                .join(file);
-            let mut local_file = std::fs::File::open(real_path)?;
-            let mut repository_file = repository.write_file(&repository_path)?;
-            std::io::copy(&mut local_file, &mut repository_file)
-                .with_context(|| format!("failed to copy {} to repository", real_path.display()))?;
-        }
-        files
-            .entry(path.clone())
-            .or_insert_with(|| HashMap::new())
-            .insert(file.clone(), metadata.clone());
-    }
-    let manifest = GameSaveManifest {
-        id: manifest_id,
-        definition: definition.clone(),
-        timestamp: time::OffsetDateTime::now_local()?,
-        files,
-    };
-    let old_manifest = read_repository_manifest(repository, &definition.name)?;
-    if !args.dry_run {
-        write_repository_manifest(&manifest, repository)?;
-        write_synced_manifest(&manifest)?;
-        match old_manifest {
-            Some(old_manifest) => repository
-                .remove(&RelativePath::new(&definition.name).join(old_manifest.id.to_string()))?,
-            None => {}
-        }
-    }
-    Ok(())
-}
-
-fn sync_game_from_repository(
-    manifest: &GameSaveManifest,
-    repository_files: &ResolvedSaveFiles,
-    repository: &impl Repository,
-    args: &SyncArgs,
-) -> Result<()> {
-    for path in &manifest.definition.paths {
-        let path = rewrite_path(&path.path)?;
-        if repository_files.contains_key(&path) {
-            continue;
-        }
-        if args.dry_run {
-            println!("- Removing save file at {}", path.display());
-        } else if path.is_file() {
-            std::fs::remove_file(&path)?;
-        } else if path.is_dir() {
-            std::fs::remove_dir_all(&path)?;
-        }
-    }
-    for (real_path, (path, file, metadata)) in repository_files {
-        if args.dry_run {
-            println!("Applying save file {}", real_path.display())
-        } else {
-            let repository_path = RelativePath::new(&manifest.definition.name)
-                .join(manifest.id.to_string())
-                .join(make_path_safe(path))
-                .join(file);
-            let mut repository_file = repository.read_file(&repository_path)?;
-            match real_path.parent() {
-                Some(parent) => std::fs::create_dir_all(parent)?,
-                None => unreachable!("save file paths must have a parent"),
-            }
-            let mut local_file = std::fs::File::create(real_path)
-                .with_context(|| format!("failed to create file at {}", real_path.display()))?;
-            std::io::copy(&mut repository_file, &mut local_file)
-                .with_context(|| format!("failed to copy {} to repository", real_path.display()))?;
-            local_file
-                .set_modified(metadata.modified.into())
-                .with_context(|| {
-                    format!("failed to set modified time on {}", real_path.display())
-                })?;
-        }
-    }
-    if !args.dry_run {
-        write_synced_manifest(&GameSaveManifest {
-            id: manifest.id,
-            definition: manifest.definition.clone(),
-            timestamp: time::OffsetDateTime::now_local()?,
-            files: manifest.files.clone(),
-        })?;
-    }
-    Ok(())
-}
-
-type ResolvedSaveFiles = HashMap<PathBuf, (String, RelativePathBuf, GameSaveFileMetadata)>;
-
-fn save_files_equal(left: &ResolvedSaveFiles, right: &ResolvedSaveFiles) -> bool {
-    if left.len() != right.len() {
-        return false;
-    }
-    for (path, (_, _, left_metadata)) in left {
-        match right.get(path) {
-            Some((_, _, right_metadata)) if left_metadata == right_metadata => {}
-            _ => return false,
-        }
-    }
-    true
-}
-
-fn get_local_files(definition: &GameDefinition) -> Result<ResolvedSaveFiles> {
-    let mut files = HashMap::new();
-    for definition_path in &definition.paths {
-        let root_path = paths::rewrite_path(&definition_path.path)?;
-        let mut queue = vec![root_path.clone()];
-        while let Some(path) = queue.pop() {
-            if files.contains_key(&path) {
-                continue;
-            }
-            if path.is_dir() {
-                for item in path.read_dir()? {
-                    queue.push(item?.path());
-                }
-            } else if path.is_file() {
-                let relative_path = path.relative_to(&root_path)?;
-                let metadata = path.metadata()?;
-                let modified = time::UtcDateTime::from(metadata.modified()?);
-                let size = metadata.len();
-                files.insert(
-                    path,
-                    (
-                        definition_path.path.clone(),
-                        relative_path,
-                        GameSaveFileMetadata { modified, size },
-                    ),
-                );
-            } else if path.exists() {
-                return Err(Error::msg(format!(
-                    "save path {} is not a file or directory",
-                    path.display()
-                )));
-            }
-        }
-    }
-    Ok(files)
-}
-
-fn get_manifest_files(manifest: &GameSaveManifest) -> Result<ResolvedSaveFiles> {
-    let mut files = HashMap::new();
-    for (definition_path, path_files) in &manifest.files {
-        let resolved_path = paths::rewrite_path(&definition_path)?;
-        for (file_path, file_metadata) in path_files {
-            files.insert(
-                file_path.to_path(&resolved_path),
-                (
-                    definition_path.clone(),
-                    file_path.clone(),
-                    file_metadata.clone(),
-                ),
-            );
-        }
-    }
-    Ok(files)
+    Ok(choice.resolution)
 }
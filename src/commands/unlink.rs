@@ -0,0 +1,22 @@
+use anyhow::{Context, Error, Result};
+use clap::Args;
+
+use gamesavesync::{games::manifest::remove_synced_manifest, utils::config};
+
+#[derive(Args, Debug)]
+pub struct UnlinkArgs {
+    #[arg(help = "The game to unlink")]
+    game: String,
+}
+
+pub fn unlink(args: &UnlinkArgs) -> Result<()> {
+    let mut config = config::load().with_context(|| "failed to load config")?;
+    if !config.linked_games.remove(&args.game) {
+        return Err(Error::msg(format!("game {} is not linked", args.game)));
+    }
+    config.wine_prefixes.remove(&args.game);
+    config::save(&config).with_context(|| "failed to save config")?;
+    remove_synced_manifest(&args.game)?;
+    println!("Unlinked {}", args.game);
+    Ok(())
+}
@@ -1,18 +1,49 @@
 use serde::{Deserialize, Serialize};
 
+pub mod blobs;
 pub mod definition;
+pub mod history;
+pub mod journal;
+pub mod ludusavi;
 pub mod manifest;
 pub mod paths;
 
 #[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
 pub enum GamePlatform {
     Linux,
+    Windows,
 }
 
+pub const GAME_PLATFORMS: [GamePlatform; 2] = [GamePlatform::Linux, GamePlatform::Windows];
+
 impl std::fmt::Display for GamePlatform {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             GamePlatform::Linux => write!(f, "Linux"),
+            GamePlatform::Windows => write!(f, "Windows"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+pub enum ConflictPolicy {
+    Ask,
+    Skip,
+    PreferLocal,
+    PreferRepository,
+    PreferNewest,
+    Fail,
+}
+
+impl std::fmt::Display for ConflictPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ConflictPolicy::Ask => write!(f, "Ask"),
+            ConflictPolicy::Skip => write!(f, "Skip"),
+            ConflictPolicy::PreferLocal => write!(f, "Prefer local"),
+            ConflictPolicy::PreferRepository => write!(f, "Prefer repository"),
+            ConflictPolicy::PreferNewest => write!(f, "Prefer newest"),
+            ConflictPolicy::Fail => write!(f, "Fail"),
         }
     }
 }
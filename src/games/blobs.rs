@@ -0,0 +1,67 @@
+use std::collections::HashSet;
+
+use anyhow::{Context, Error, Result};
+use relative_path::{RelativePath, RelativePathBuf};
+
+use crate::{repository::Repository, utils::paths::make_path_safe};
+
+use super::{
+    history::list_snapshots,
+    manifest::{GameSaveFileMetadata, GameSaveManifest, GameSaveStorage},
+};
+
+const BLOBS_DIR: &str = "blobs";
+
+pub fn blob_path(game: &str, hash: &str) -> RelativePathBuf {
+    RelativePath::new(game).join(BLOBS_DIR).join(hash)
+}
+
+pub fn stored_file_path(
+    manifest: &GameSaveManifest,
+    path: &str,
+    file: &RelativePath,
+    metadata: &GameSaveFileMetadata,
+) -> Result<RelativePathBuf> {
+    match manifest.storage {
+        GameSaveStorage::Directory => Ok(RelativePath::new(&manifest.definition.name)
+            .join(manifest.id.to_string())
+            .join(make_path_safe(path))
+            .join(file)),
+        GameSaveStorage::Blobs => match &metadata.hash {
+            Some(hash) => Ok(blob_path(&manifest.definition.name, hash)),
+            None => Err(Error::msg(format!(
+                "{file} in snapshot {} has no content hash",
+                manifest.id
+            ))),
+        },
+    }
+}
+
+pub fn collect_garbage(repository: &impl Repository, game: &str) -> Result<Vec<String>> {
+    let dir = RelativePath::new(game).join(BLOBS_DIR);
+    if !repository.is_dir(&dir)? {
+        return Ok(vec![]);
+    }
+    let mut referenced = HashSet::new();
+    for snapshot in list_snapshots(repository, game)? {
+        if snapshot.storage != GameSaveStorage::Blobs {
+            continue;
+        }
+        for files in snapshot.files.values() {
+            referenced.extend(files.values().filter_map(|metadata| metadata.hash.clone()));
+        }
+    }
+    let mut removed = vec![];
+    for path in repository.read_dir(&dir)? {
+        let hash = match path?.file_name() {
+            Some(hash) if !referenced.contains(hash) => hash.to_string(),
+            Some(_) => continue,
+            None => unreachable!("read_dir should never return an empty path"),
+        };
+        repository
+            .remove(&blob_path(game, &hash))
+            .with_context(|| format!("failed to remove blob {hash}"))?;
+        removed.push(hash);
+    }
+    Ok(removed)
+}
@@ -1,15 +1,84 @@
 use anyhow::{Context, Error, Result};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
 use relative_path::RelativePath;
 use serde::{Deserialize, Serialize};
 
-use super::GamePlatform;
-use crate::repository::{Repository, RepositoryExt};
+use super::{ConflictPolicy, GamePlatform};
+use crate::{
+    repository::{Repository, RepositoryExt, settings::SETTINGS_FILE},
+    utils::paths::make_path_safe,
+};
 
 const DEFINITION_FILE: &str = "definition.toml";
 
 #[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
 pub struct GameDefinitionPath {
     pub path: String,
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+impl GameDefinitionPath {
+    pub fn new(path: String) -> Self {
+        GameDefinitionPath {
+            path,
+            include: vec![],
+            exclude: vec![],
+        }
+    }
+
+    pub fn filter(&self) -> Result<SaveFileFilter> {
+        let include = if self.include.is_empty() {
+            None
+        } else {
+            Some(build_glob_set(&self.include)?)
+        };
+        let exclude = build_glob_set(&self.exclude)?;
+        Ok(SaveFileFilter { include, exclude })
+    }
+}
+
+pub struct SaveFileFilter {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+impl SaveFileFilter {
+    pub fn matches(&self, path: &RelativePath) -> bool {
+        // A save path pointing at a single file is never filtered
+        if path.as_str().is_empty() {
+            return true;
+        }
+        let included = match &self.include {
+            Some(include) => include.is_match(path.as_str()),
+            None => true,
+        };
+        included && !self.exclude.is_match(path.as_str())
+    }
+}
+
+// Globs follow gitignore: without a slash they match names at any depth, otherwise the whole path
+fn build_glob_set(globs: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        let mut pattern = glob.clone();
+        if pattern.ends_with('/') {
+            pattern.push_str("**");
+        }
+        let pattern = match pattern.strip_prefix('/') {
+            Some(anchored) => anchored.to_string(),
+            None if !pattern.contains('/') => format!("**/{pattern}"),
+            None => pattern,
+        };
+        let glob = GlobBuilder::new(&pattern)
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("invalid glob {glob}"))?;
+        builder.add(glob);
+    }
+    builder.build().with_context(|| "failed to build globs")
 }
 
 #[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
@@ -19,11 +88,32 @@ pub struct GameDefinition {
     #[serde(default)]
     pub paths: Vec<GameDefinitionPath>,
     pub steam_app_id: Option<u64>,
+    pub conflict_policy: Option<ConflictPolicy>,
+}
+
+// Game names are used as directory names in the repository and the local data directory
+pub fn check_game_name(game: &str) -> Result<()> {
+    if game.is_empty() || make_path_safe(game) != game {
+        return Err(Error::msg(format!(
+            "game name {game} contains invalid characters"
+        )));
+    }
+    if game.starts_with('.') {
+        return Err(Error::msg(format!(
+            "game name {game} must not start with a dot"
+        )));
+    }
+    if game.eq_ignore_ascii_case(SETTINGS_FILE) {
+        return Err(Error::msg(format!(
+            "game name {game} is used by the repository"
+        )));
+    }
+    Ok(())
 }
 
 pub fn definition_exists(repository: &impl Repository, game: &str) -> Result<bool> {
     let path = RelativePath::new(&game).join(DEFINITION_FILE);
-    return Ok(repository.is_file(&path)?);
+    repository.is_file(&path)
 }
 
 pub fn list_definitions(repository: &impl Repository) -> Result<Vec<String>> {
@@ -50,3 +140,82 @@ pub fn load_definition(repository: &impl Repository, game: &str) -> Result<GameD
         .with_context(|| format!("failed to read definition for {game}"))?;
     toml::from_str(&file).with_context(|| format!("failed to parse definition for {game}"))
 }
+
+pub fn write_definition(repository: &impl Repository, definition: &GameDefinition) -> Result<()> {
+    let path = RelativePath::new(&definition.name).join(DEFINITION_FILE);
+    let serialized = toml::to_string_pretty(definition)
+        .with_context(|| format!("failed to serialize definition for {}", definition.name))?;
+    repository
+        .write_string(&path, &serialized)
+        .with_context(|| format!("failed to write definition for {}", definition.name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(include: &[&str], exclude: &[&str]) -> SaveFileFilter {
+        GameDefinitionPath {
+            include: include.iter().map(|glob| glob.to_string()).collect(),
+            exclude: exclude.iter().map(|glob| glob.to_string()).collect(),
+            ..GameDefinitionPath::new("<home>/saves".into())
+        }
+        .filter()
+        .unwrap()
+    }
+
+    #[test]
+    fn filter_without_globs_matches_everything() {
+        let filter = filter(&[], &[]);
+        assert!(filter.matches(RelativePath::new("slot1.sav")));
+        assert!(filter.matches(RelativePath::new("logs/today.log")));
+    }
+
+    #[test]
+    fn filter_name_globs_match_at_any_depth() {
+        let filter = filter(&["*.sav"], &["*.tmp"]);
+        assert!(filter.matches(RelativePath::new("slot1.sav")));
+        assert!(filter.matches(RelativePath::new("profiles/a/slot1.sav")));
+        assert!(!filter.matches(RelativePath::new("options.ini")));
+        assert!(!filter.matches(RelativePath::new("slot1.sav.tmp")));
+    }
+
+    #[test]
+    fn filter_path_globs_are_anchored() {
+        let anchored = filter(&["/*.sav"], &[]);
+        assert!(anchored.matches(RelativePath::new("slot1.sav")));
+        assert!(!anchored.matches(RelativePath::new("profiles/slot1.sav")));
+        let excluded = filter(&[], &["logs/**", "cache/"]);
+        assert!(!excluded.matches(RelativePath::new("logs/today.log")));
+        assert!(!excluded.matches(RelativePath::new("cache/shaders/a.bin")));
+        assert!(excluded.matches(RelativePath::new("saves/logs.sav")));
+    }
+
+    #[test]
+    fn filter_never_excludes_a_single_file_path() {
+        assert!(filter(&["*.sav"], &["*"]).matches(RelativePath::new("")));
+    }
+
+    #[test]
+    fn game_names_stay_inside_their_directory() {
+        assert!(check_game_name("Some Game: Remastered").is_err());
+        assert!(check_game_name("Some/Game").is_err());
+        assert!(check_game_name("").is_err());
+        assert!(check_game_name(".").is_err());
+        assert!(check_game_name("..").is_err());
+        assert!(check_game_name(".git").is_err());
+        assert!(check_game_name("GameSaveSync.toml").is_err());
+        assert!(check_game_name("gamesavesync.TOML").is_err());
+        assert!(check_game_name("Some Game").is_ok());
+        assert!(check_game_name("Some Game 2.0").is_ok());
+    }
+
+    #[test]
+    fn filter_rejects_invalid_globs() {
+        let path = GameDefinitionPath {
+            exclude: vec!["[".into()],
+            ..GameDefinitionPath::new("<home>/saves".into())
+        };
+        assert!(path.filter().is_err());
+    }
+}
@@ -0,0 +1,314 @@
+use std::collections::HashSet;
+
+use anyhow::{Context, Result};
+use relative_path::{RelativePath, RelativePathBuf};
+use uuid::Uuid;
+
+use crate::repository::{Repository, RepositoryExt, settings::RetentionPolicy};
+
+use super::manifest::{GameSaveManifest, read_repository_manifest};
+
+const SNAPSHOTS_DIR: &str = "snapshots";
+
+fn snapshot_path(game: &str, id: Uuid) -> RelativePathBuf {
+    RelativePath::new(game)
+        .join(SNAPSHOTS_DIR)
+        .join(format!("{id}.toml"))
+}
+
+pub fn snapshot_exists(repository: &impl Repository, game: &str, id: Uuid) -> Result<bool> {
+    repository.is_file(&snapshot_path(game, id))
+}
+
+pub fn read_snapshot_manifest(
+    repository: &impl Repository,
+    game: &str,
+    id: Uuid,
+) -> Result<Option<GameSaveManifest>> {
+    let path = snapshot_path(game, id);
+    if !repository.is_file(&path)? {
+        return match read_repository_manifest(repository, game)? {
+            Some(manifest) if manifest.id == id => Ok(Some(manifest)),
+            _ => Ok(None),
+        };
+    }
+    let file = repository
+        .read_string(&path)
+        .with_context(|| format!("failed to read snapshot {id} for {game}"))?;
+    let manifest = toml::from_str(&file)
+        .with_context(|| format!("failed to parse snapshot {id} for {game}"))?;
+    Ok(Some(manifest))
+}
+
+pub fn write_snapshot_manifest(
+    manifest: &GameSaveManifest,
+    repository: &impl Repository,
+) -> Result<()> {
+    let path = snapshot_path(&manifest.definition.name, manifest.id);
+    let serialized = toml::to_string_pretty(manifest)
+        .with_context(|| format!("failed to serialize snapshot {}", manifest.id))?;
+    repository
+        .write_string(&path, &serialized)
+        .with_context(|| format!("failed to write snapshot {}", manifest.id))
+}
+
+pub fn list_snapshots(repository: &impl Repository, game: &str) -> Result<Vec<GameSaveManifest>> {
+    let mut snapshots = vec![];
+    let dir = RelativePath::new(game).join(SNAPSHOTS_DIR);
+    if repository.is_dir(&dir)? {
+        for path in repository.read_dir(&dir)? {
+            let path = path?;
+            let id = match path.file_stem().map(Uuid::parse_str) {
+                Some(Ok(id)) if path.extension() == Some("toml") => id,
+                _ => continue,
+            };
+            if let Some(manifest) = read_snapshot_manifest(repository, game, id)? {
+                snapshots.push(manifest);
+            }
+        }
+    }
+    if let Some(head) = read_repository_manifest(repository, game)?
+        && !snapshots.iter().any(|snapshot| snapshot.id == head.id)
+    {
+        snapshots.push(head);
+    }
+    snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.timestamp));
+    Ok(snapshots)
+}
+
+pub fn retained_snapshots(
+    snapshots: &[GameSaveManifest],
+    policy: &RetentionPolicy,
+) -> HashSet<Uuid> {
+    if policy.keep_last.is_none() && policy.keep_daily.is_none() && policy.keep_weekly.is_none() {
+        return snapshots.iter().map(|snapshot| snapshot.id).collect();
+    }
+    let mut snapshots: Vec<_> = snapshots
+        .iter()
+        .filter(|snapshot| snapshot.branch.is_none())
+        .collect();
+    snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.timestamp));
+    let mut retained = HashSet::new();
+    if let Some(keep_last) = policy.keep_last {
+        retained.extend(snapshots.iter().take(keep_last).map(|snapshot| snapshot.id));
+    }
+    if let Some(keep_daily) = policy.keep_daily {
+        let mut days = HashSet::new();
+        for snapshot in &snapshots {
+            if days.len() >= keep_daily {
+                break;
+            }
+            if days.insert(snapshot.timestamp.date()) {
+                retained.insert(snapshot.id);
+            }
+        }
+    }
+    if let Some(keep_weekly) = policy.keep_weekly {
+        let mut weeks = HashSet::new();
+        for snapshot in snapshots {
+            if weeks.len() >= keep_weekly {
+                break;
+            }
+            let (year, week, _) = snapshot.timestamp.to_iso_week_date();
+            if weeks.insert((year, week)) {
+                retained.insert(snapshot.id);
+            }
+        }
+    }
+    retained
+}
+
+// Blobs that are no longer referenced are left for collect_garbage
+pub fn remove_snapshot(repository: &impl Repository, game: &str, id: Uuid) -> Result<()> {
+    repository
+        .remove(&RelativePath::new(game).join(id.to_string()))
+        .with_context(|| format!("failed to remove files for snapshot {id}"))?;
+    repository
+        .remove(&snapshot_path(game, id))
+        .with_context(|| format!("failed to remove snapshot {id}"))
+}
+
+pub fn prune_snapshots(
+    repository: &impl Repository,
+    game: &str,
+    policy: &RetentionPolicy,
+) -> Result<Vec<Uuid>> {
+    let snapshots = list_snapshots(repository, game)?;
+    let mut retained = retained_snapshots(&snapshots, policy);
+    if let Some(head) = read_repository_manifest(repository, game)? {
+        retained.insert(head.id);
+    }
+    let mut pruned = vec![];
+    for snapshot in snapshots {
+        if retained.contains(&snapshot.id) || snapshot.branch.is_some() {
+            continue;
+        }
+        remove_snapshot(repository, game, snapshot.id)?;
+        pruned.push(snapshot.id);
+    }
+    Ok(pruned)
+}
+
+pub fn find_branch(
+    repository: &impl Repository,
+    game: &str,
+    branch: &str,
+) -> Result<Option<GameSaveManifest>> {
+    Ok(list_snapshots(repository, game)?
+        .into_iter()
+        .find(|snapshot| snapshot.branch.as_deref() == Some(branch)))
+}
+
+#[cfg(test)]
+mod tests {
+    use time::{OffsetDateTime, macros::datetime};
+
+    use super::*;
+    use crate::{
+        games::{
+            GamePlatform,
+            definition::GameDefinition,
+            manifest::{GameSaveManifest, GameSaveStorage},
+        },
+        repository::memory_repository::MemoryRepository,
+    };
+
+    const GAME: &str = "Example";
+
+    fn snapshot(timestamp: OffsetDateTime) -> GameSaveManifest {
+        GameSaveManifest {
+            id: Uuid::new_v4(),
+            parent: None,
+            device: None,
+            branch: None,
+            storage: GameSaveStorage::Blobs,
+            definition: GameDefinition {
+                name: GAME.into(),
+                platform: GamePlatform::Linux,
+                paths: vec![],
+                steam_app_id: None,
+                conflict_policy: None,
+            },
+            timestamp,
+            files: Default::default(),
+        }
+    }
+
+    fn policy(
+        keep_last: Option<usize>,
+        keep_daily: Option<usize>,
+        keep_weekly: Option<usize>,
+    ) -> RetentionPolicy {
+        RetentionPolicy {
+            keep_last,
+            keep_daily,
+            keep_weekly,
+        }
+    }
+
+    fn ids(snapshots: &[&GameSaveManifest]) -> HashSet<Uuid> {
+        snapshots.iter().map(|snapshot| snapshot.id).collect()
+    }
+
+    #[test]
+    fn keep_last_retains_newest() {
+        let oldest = snapshot(datetime!(2024-03-01 12:00 UTC));
+        let older = snapshot(datetime!(2024-03-02 12:00 UTC));
+        let newest = snapshot(datetime!(2024-03-03 12:00 UTC));
+        let snapshots = [older.clone(), newest.clone(), oldest.clone()];
+        assert_eq!(
+            retained_snapshots(&snapshots, &policy(Some(2), None, None)),
+            ids(&[&newest, &older])
+        );
+        assert_eq!(
+            retained_snapshots(&snapshots, &policy(None, None, None)),
+            ids(&[&oldest, &older, &newest])
+        );
+    }
+
+    #[test]
+    fn keep_daily_retains_newest_of_each_day() {
+        let monday_night = snapshot(datetime!(2024-03-04 23:59:59 UTC));
+        let tuesday_midnight = snapshot(datetime!(2024-03-05 00:00 UTC));
+        let tuesday_noon = snapshot(datetime!(2024-03-05 12:00 UTC));
+        let wednesday = snapshot(datetime!(2024-03-06 08:00 UTC));
+        let snapshots = [
+            monday_night.clone(),
+            tuesday_midnight.clone(),
+            tuesday_noon.clone(),
+            wednesday.clone(),
+        ];
+        assert_eq!(
+            retained_snapshots(&snapshots, &policy(None, Some(2), None)),
+            ids(&[&wednesday, &tuesday_noon])
+        );
+        assert_eq!(
+            retained_snapshots(&snapshots, &policy(None, Some(3), None)),
+            ids(&[&wednesday, &tuesday_noon, &monday_night])
+        );
+        // Each rule keeps its own snapshots, and the union is retained
+        assert_eq!(
+            retained_snapshots(&snapshots, &policy(Some(2), Some(3), None)),
+            ids(&[&wednesday, &tuesday_noon, &monday_night])
+        );
+    }
+
+    #[test]
+    fn keep_weekly_retains_newest_of_each_iso_week() {
+        let sunday = snapshot(datetime!(2024-03-03 23:59:59 UTC));
+        let monday = snapshot(datetime!(2024-03-04 00:00 UTC));
+        let next_sunday = snapshot(datetime!(2024-03-10 12:00 UTC));
+        let next_monday = snapshot(datetime!(2024-03-11 00:00 UTC));
+        let snapshots = [
+            sunday.clone(),
+            monday.clone(),
+            next_sunday.clone(),
+            next_monday.clone(),
+        ];
+        assert_eq!(
+            retained_snapshots(&snapshots, &policy(None, None, Some(2))),
+            ids(&[&next_monday, &next_sunday])
+        );
+        assert_eq!(
+            retained_snapshots(&snapshots, &policy(None, None, Some(3))),
+            ids(&[&next_monday, &next_sunday, &sunday])
+        );
+    }
+
+    #[test]
+    fn keep_weekly_follows_iso_weeks_across_years() {
+        // 2024-12-30 is a Monday and already in the first week of 2025
+        let last_week = snapshot(datetime!(2024-12-29 12:00 UTC));
+        let first_week = snapshot(datetime!(2024-12-30 12:00 UTC));
+        let new_year = snapshot(datetime!(2025-01-01 12:00 UTC));
+        let snapshots = [last_week.clone(), first_week.clone(), new_year.clone()];
+        assert_eq!(
+            retained_snapshots(&snapshots, &policy(None, None, Some(2))),
+            ids(&[&new_year, &last_week])
+        );
+    }
+
+    #[test]
+    fn branches_are_not_counted_or_pruned() {
+        let repository = MemoryRepository::new();
+        let old = snapshot(datetime!(2024-03-01 12:00 UTC));
+        let current = snapshot(datetime!(2024-03-02 12:00 UTC));
+        let branch = GameSaveManifest {
+            branch: Some("conflict-device".into()),
+            ..snapshot(datetime!(2024-03-03 12:00 UTC))
+        };
+        for snapshot in [&old, &current, &branch] {
+            write_snapshot_manifest(snapshot, &repository).unwrap();
+        }
+        let snapshots = list_snapshots(&repository, GAME).unwrap();
+        assert_eq!(
+            retained_snapshots(&snapshots, &policy(Some(1), Some(1), Some(1))),
+            ids(&[&current])
+        );
+        let pruned = prune_snapshots(&repository, GAME, &policy(Some(1), None, None)).unwrap();
+        assert_eq!(pruned, vec![old.id]);
+        assert!(snapshot_exists(&repository, GAME, branch.id).unwrap());
+        assert!(snapshot_exists(&repository, GAME, current.id).unwrap());
+        assert!(!snapshot_exists(&repository, GAME, old.id).unwrap());
+    }
+}
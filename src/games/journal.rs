@@ -0,0 +1,97 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::{
+    files::{remove_temp_files, write_file_atomic},
+    paths::data_dir,
+};
+
+const JOURNAL_DIR: &str = "journal";
+const JOURNAL_FILE: &str = "journal.toml";
+
+#[derive(Serialize, Deserialize, Debug)]
+struct ApplyJournalEntry {
+    path: PathBuf,
+    backup: Option<PathBuf>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct ApplyJournal {
+    entries: Vec<ApplyJournalEntry>,
+}
+
+fn copy_file(from: &Path, to: &Path) -> Result<()> {
+    let modified = from.metadata()?.modified()?;
+    let mut file =
+        std::fs::File::open(from).with_context(|| format!("failed to open {}", from.display()))?;
+    write_file_atomic(to, &mut file, Some(modified))
+}
+
+pub fn begin_apply<'a>(game: &str, paths: impl IntoIterator<Item = &'a Path>) -> Result<()> {
+    let dir = data_dir()?.join(game).join(JOURNAL_DIR);
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).with_context(|| "failed to clear old sync journal")?;
+    }
+    let mut entries = vec![];
+    let mut seen = HashSet::new();
+    for path in paths {
+        if !seen.insert(path) {
+            continue;
+        }
+        let backup = if path.is_file() {
+            let backup = dir.join(entries.len().to_string());
+            copy_file(path, &backup)
+                .with_context(|| format!("failed to back up {}", path.display()))?;
+            Some(backup)
+        } else {
+            None
+        };
+        entries.push(ApplyJournalEntry {
+            path: path.to_path_buf(),
+            backup,
+        });
+    }
+    // The journal is written last so it only exists once every backup is complete
+    let serialized = toml::to_string_pretty(&ApplyJournal { entries })
+        .with_context(|| "failed to serialize sync journal")?;
+    write_file_atomic(&dir.join(JOURNAL_FILE), &mut serialized.as_bytes(), None)
+        .with_context(|| "failed to write sync journal")
+}
+
+pub fn finish_apply(game: &str) -> Result<()> {
+    let dir = data_dir()?.join(game).join(JOURNAL_DIR);
+    let path = dir.join(JOURNAL_FILE);
+    if path.is_file() {
+        std::fs::remove_file(&path).with_context(|| "failed to remove sync journal")?;
+    }
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).with_context(|| "failed to remove sync journal")?;
+    }
+    Ok(())
+}
+
+pub fn rollback_apply(game: &str) -> Result<bool> {
+    let path = data_dir()?.join(game).join(JOURNAL_DIR).join(JOURNAL_FILE);
+    if !path.is_file() {
+        finish_apply(game)?;
+        return Ok(false);
+    }
+    let file = std::fs::read_to_string(&path).with_context(|| "failed to read sync journal")?;
+    let journal: ApplyJournal =
+        toml::from_str(&file).with_context(|| "failed to parse sync journal")?;
+    for entry in journal.entries {
+        remove_temp_files(&entry.path)?;
+        match entry.backup {
+            Some(backup) => copy_file(&backup, &entry.path)
+                .with_context(|| format!("failed to roll back {}", entry.path.display()))?,
+            None if entry.path.is_file() => std::fs::remove_file(&entry.path)
+                .with_context(|| format!("failed to roll back {}", entry.path.display()))?,
+            None => {}
+        }
+    }
+    finish_apply(game)?;
+    Ok(true)
+}
@@ -0,0 +1,446 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use fuzzy_matcher::{FuzzyMatcher, skim::SkimMatcherV2};
+use serde::Deserialize;
+use serde::de::IgnoredAny;
+
+use super::{
+    GamePlatform,
+    definition::{GameDefinition, GameDefinitionPath},
+    paths::{STEAM_LIBRARY, find_steam_library, rewrite_path, steam_roots},
+};
+
+pub type LudusaviManifest = BTreeMap<String, LudusaviGame>;
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LudusaviGame {
+    pub alias: Option<String>,
+    #[serde(default)]
+    pub files: BTreeMap<String, LudusaviFileRule>,
+    #[serde(default)]
+    pub install_dir: BTreeMap<String, IgnoredAny>,
+    #[serde(default)]
+    pub registry: BTreeMap<String, IgnoredAny>,
+    pub steam: Option<LudusaviSteam>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+pub struct LudusaviFileRule {
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub when: Vec<LudusaviConstraint>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+pub struct LudusaviConstraint {
+    pub os: Option<String>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+pub struct LudusaviSteam {
+    pub id: Option<u64>,
+}
+
+pub struct LudusaviImport {
+    pub definition: GameDefinition,
+    pub unsupported: Vec<String>,
+}
+
+const MAX_SEARCH_RESULTS: usize = 10;
+
+pub fn load_ludusavi_manifest(path: &Path) -> Result<LudusaviManifest> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("failed to open Ludusavi manifest {}", path.display()))?;
+    serde_yaml_ng::from_reader(std::io::BufReader::new(file))
+        .with_context(|| format!("failed to parse Ludusavi manifest {}", path.display()))
+}
+
+pub fn search_titles<'a>(manifest: &'a LudusaviManifest, query: &str) -> Vec<&'a str> {
+    if let Some((title, _)) = manifest
+        .iter()
+        .find(|(title, _)| title.eq_ignore_ascii_case(query))
+    {
+        return vec![title];
+    }
+    let matcher = SkimMatcherV2::default().ignore_case();
+    let mut matches: Vec<_> = manifest
+        .keys()
+        .filter_map(|title| Some((matcher.fuzzy_match(title, query)?, title.as_str())))
+        .collect();
+    matches.sort_by_key(|(score, title)| (std::cmp::Reverse(*score), title.len()));
+    matches
+        .into_iter()
+        .take(MAX_SEARCH_RESULTS)
+        .map(|(_, title)| title)
+        .collect()
+}
+
+pub fn find_game<'a>(
+    manifest: &'a LudusaviManifest,
+    title: &'a str,
+) -> Option<(&'a str, &'a LudusaviGame)> {
+    let (title, game) = manifest.get_key_value(title)?;
+    match &game.alias {
+        Some(alias) => manifest
+            .get_key_value(alias)
+            .map(|(title, game)| (title.as_str(), game)),
+        None => Some((title, game)),
+    }
+}
+
+fn platform_os(platform: GamePlatform) -> &'static str {
+    match platform {
+        GamePlatform::Linux => "linux",
+        GamePlatform::Windows => "windows",
+    }
+}
+
+pub fn default_platform(game: &LudusaviGame) -> GamePlatform {
+    let has_linux_rules = game.files.values().any(|rule| {
+        rule.when
+            .iter()
+            .any(|constraint| constraint.os.as_deref() == Some("linux"))
+    });
+    if has_linux_rules {
+        GamePlatform::Linux
+    } else {
+        GamePlatform::Windows
+    }
+}
+
+fn convert_segment(
+    segment: &str,
+    game: &LudusaviGame,
+    platform: GamePlatform,
+) -> Result<String, String> {
+    match segment {
+        "<home>" => Ok(segment.into()),
+        "<xdgData>" | "<xdgConfig>" if platform == GamePlatform::Linux => Ok(segment.into()),
+        "<winAppData>"
+        | "<winLocalAppData>"
+        | "<winLocalAppDataLow>"
+        | "<winDocuments>"
+        | "<winPublic>"
+        | "<winProgramData>"
+            if platform == GamePlatform::Windows =>
+        {
+            Ok(segment.into())
+        }
+        "<game>" => match game.install_dir.keys().next() {
+            Some(install_dir) => Ok(install_dir.clone()),
+            None => Err("the game has no install folder".into()),
+        },
+        "<storeGameId>" => match game.steam.as_ref().and_then(|steam| steam.id) {
+            Some(steam_app_id) => Ok(steam_app_id.to_string()),
+            None => Err("the game has no Steam app id".into()),
+        },
+        "<root>" | "<base>" => find_install(segment, game),
+        _ if segment.starts_with('<') && segment.ends_with('>') => {
+            Err(format!("{segment} is not supported on {platform}"))
+        }
+        _ => Ok(segment.into()),
+    }
+}
+
+// The install has to be in one of the local Steam libraries, which is found again on each device
+fn find_install(segment: &str, game: &LudusaviGame) -> Result<String, String> {
+    if game.install_dir.is_empty() {
+        return Err("the game has no install folder".into());
+    }
+    let steam_roots = steam_roots().map_err(|error| error.to_string())?;
+    let Some(install_dir) = game
+        .install_dir
+        .keys()
+        .find(|install_dir| find_steam_library(&steam_roots, install_dir).is_some())
+    else {
+        return Err("the game is not installed in a Steam library".into());
+    };
+    match segment {
+        "<root>" => Ok(STEAM_LIBRARY.into()),
+        _ => Ok(format!("{STEAM_LIBRARY}/steamapps/common/{install_dir}")),
+    }
+}
+
+// The store user id is the same on every device, so it can be taken from the local folders
+fn find_store_user_id(prefix: &str, definition: &GameDefinition) -> Result<String, String> {
+    let parent = rewrite_path(prefix, definition).map_err(|error| error.to_string())?;
+    let mut user_ids = parent
+        .read_dir()
+        .map_err(|_| format!("{} does not exist", parent.display()))?
+        .filter_map(|item| item.ok())
+        .filter(|item| item.path().is_dir())
+        .filter_map(|item| item.file_name().into_string().ok());
+    match (user_ids.next(), user_ids.next()) {
+        (Some(user_id), None) => Ok(user_id),
+        (None, _) => Err(format!("no store user id found in {}", parent.display())),
+        (Some(_), Some(_)) => Err(format!(
+            "more than one store user id found in {}",
+            parent.display()
+        )),
+    }
+}
+
+fn is_glob(segment: &str) -> bool {
+    segment.contains(['*', '?', '['])
+}
+
+// Anything from the first wildcard on becomes an include glob below the save path
+fn convert_path(
+    path: &str,
+    game: &LudusaviGame,
+    definition: &GameDefinition,
+) -> Result<GameDefinitionPath, String> {
+    let mut converted: Vec<String> = vec![];
+    let mut segments = path.split('/');
+    let mut include = vec![];
+    for segment in segments.by_ref() {
+        if is_glob(segment) {
+            include.push(segment.to_string());
+            break;
+        }
+        let segment = match segment {
+            "<storeUserId>" => find_store_user_id(&converted.join("/"), definition)?,
+            _ => convert_segment(segment, game, definition.platform)?,
+        };
+        converted.push(segment);
+    }
+    for segment in segments {
+        if segment.starts_with('<') && segment.ends_with('>') {
+            return Err(format!("{segment} after a wildcard is not supported"));
+        }
+        include.push(segment.to_string());
+    }
+    let converted = GameDefinitionPath {
+        include: if include.is_empty() {
+            vec![]
+        } else {
+            vec![format!("/{}", include.join("/"))]
+        },
+        ..GameDefinitionPath::new(converted.join("/"))
+    };
+    rewrite_path(&converted.path, definition).map_err(|error| error.to_string())?;
+    converted.filter().map_err(|error| format!("{error:#}"))?;
+    Ok(converted)
+}
+
+pub fn convert_game(name: &str, game: &LudusaviGame, platform: GamePlatform) -> LudusaviImport {
+    let mut definition = GameDefinition {
+        name: name.into(),
+        platform,
+        paths: vec![],
+        steam_app_id: game.steam.as_ref().and_then(|steam| steam.id),
+        conflict_policy: None,
+    };
+    let mut unsupported = vec![];
+    for (path, rule) in &game.files {
+        let applies = rule.when.is_empty()
+            || rule.when.iter().any(|constraint| match &constraint.os {
+                Some(os) => os == platform_os(platform),
+                None => true,
+            });
+        if !applies {
+            let systems: Vec<_> = rule
+                .when
+                .iter()
+                .filter_map(|constraint| constraint.os.as_deref())
+                .collect();
+            unsupported.push(format!("{path}: only applies to {}", systems.join(", ")));
+            continue;
+        }
+        if !rule.tags.is_empty() && !rule.tags.iter().any(|tag| tag == "save") {
+            unsupported.push(format!("{path}: not tagged as a save"));
+            continue;
+        }
+        match convert_path(path, game, &definition) {
+            Ok(converted) => match definition
+                .paths
+                .iter_mut()
+                .find(|existing| existing.path == converted.path)
+            {
+                // An empty include list already takes every file
+                Some(existing) if existing.include.is_empty() => {}
+                Some(existing) if converted.include.is_empty() => existing.include.clear(),
+                Some(existing) => existing.include.extend(converted.include),
+                None => definition.paths.push(converted),
+            },
+            Err(reason) => unsupported.push(format!("{path}: {reason}")),
+        }
+    }
+    for key in game.registry.keys() {
+        unsupported.push(format!("{key}: registry keys are not supported"));
+    }
+    LudusaviImport {
+        definition,
+        unsupported,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{
+        config::{self, Config},
+        paths::{DirOverrides, override_dirs},
+    };
+
+    fn fixture() -> LudusaviManifest {
+        serde_yaml_ng::from_str(include_str!("../../tests/fixtures/ludusavi.yaml")).unwrap()
+    }
+
+    fn use_home(home: &Path) {
+        override_dirs(Some(DirOverrides {
+            home: home.into(),
+            config: home.join("config"),
+            data: home.join("data"),
+        }));
+    }
+
+    fn use_prefix(game: &str, prefix: &Path) {
+        config::save(&Config {
+            wine_prefixes: [(game.into(), prefix.to_string_lossy().into())].into(),
+            ..Default::default()
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn search_prefers_exact_title() {
+        let manifest = fixture();
+        assert_eq!(
+            search_titles(&manifest, "example game"),
+            vec!["Example Game"]
+        );
+    }
+
+    #[test]
+    fn search_matches_fuzzy_title() {
+        let manifest = fixture();
+        let titles = search_titles(&manifest, "exmpl remast");
+        assert_eq!(titles.first(), Some(&"Example Game Remastered"));
+        assert!(search_titles(&manifest, "unrelated").is_empty());
+    }
+
+    #[test]
+    fn find_game_follows_alias() {
+        let manifest = fixture();
+        let (title, game) = find_game(&manifest, "Old Example").unwrap();
+        assert_eq!(title, "Example Game");
+        assert_eq!(default_platform(game), GamePlatform::Linux);
+        let (_, game) = find_game(&manifest, "Example Game Remastered").unwrap();
+        assert_eq!(default_platform(game), GamePlatform::Windows);
+    }
+
+    #[test]
+    fn convert_linux_game() {
+        let manifest = fixture();
+        let home = tempfile::tempdir().unwrap();
+        use_home(home.path());
+        let import = convert_game("Example", &manifest["Example Game"], GamePlatform::Linux);
+        assert_eq!(import.definition.platform, GamePlatform::Linux);
+        assert_eq!(import.definition.steam_app_id, Some(123450));
+        assert_eq!(
+            import.definition.paths,
+            vec![GameDefinitionPath::new(
+                "<home>/.local/share/example/saves".into()
+            )]
+        );
+        let skipped = |rule: &str| import.unsupported.iter().any(|line| line.starts_with(rule));
+        assert!(skipped(
+            "<xdgConfig>/example/settings.ini: not tagged as a save"
+        ));
+        assert!(skipped(
+            "<winAppData>/Example/<storeUserId>/saves: only applies to windows"
+        ));
+        assert!(skipped(
+            "<base>/saves: the game is not installed in a Steam library"
+        ));
+        assert!(skipped(
+            "<home>/Library/Application Support/Example: only applies to mac"
+        ));
+        assert!(skipped("HKEY_CURRENT_USER/Software/Example: registry keys"));
+    }
+
+    #[test]
+    fn convert_install_folder_in_steam_library() {
+        let manifest = fixture();
+        let game = &manifest["Example Game"];
+        let home = tempfile::tempdir().unwrap();
+        use_home(home.path());
+        let steam_root = home.path().join(".steam/steam");
+        std::fs::create_dir_all(steam_root.join("steamapps/common/Example Game")).unwrap();
+        let import = convert_game("Example", game, GamePlatform::Linux);
+        let installed =
+            GameDefinitionPath::new("<steamLibrary>/steamapps/common/Example Game/saves".into());
+        assert!(import.definition.paths.contains(&installed));
+        assert_eq!(
+            convert_segment("<root>", game, GamePlatform::Linux),
+            Ok("<steamLibrary>".into())
+        );
+
+        // The library is left for each device to find, wherever this one keeps it
+        let library = tempfile::tempdir().unwrap();
+        std::fs::remove_dir_all(steam_root.join("steamapps/common")).unwrap();
+        std::fs::create_dir_all(library.path().join("steamapps/common/Example Game")).unwrap();
+        std::fs::write(
+            steam_root.join("steamapps/libraryfolders.vdf"),
+            format!(
+                "\"libraryfolders\"\n{{\n\t\"1\"\n\t{{\n\t\t\"path\"\t\t\"{}\"\n\t}}\n}}\n",
+                library.path().display()
+            ),
+        )
+        .unwrap();
+        let import = convert_game("Example", game, GamePlatform::Windows);
+        assert!(import.definition.paths.contains(&installed));
+    }
+
+    #[test]
+    fn convert_windows_game_in_prefix() {
+        let manifest = fixture();
+        let home = tempfile::tempdir().unwrap();
+        let prefix = tempfile::tempdir().unwrap();
+        use_home(home.path());
+        let app_data = prefix
+            .path()
+            .join("drive_c/users/steamuser/AppData/Roaming");
+        std::fs::create_dir_all(app_data.join("Example/76561198000000000")).unwrap();
+        use_prefix("Example", prefix.path());
+        let import = convert_game("Example", &manifest["Example Game"], GamePlatform::Windows);
+        assert_eq!(
+            import.definition.paths,
+            vec![GameDefinitionPath::new(
+                "<winAppData>/Example/76561198000000000/saves".into()
+            )]
+        );
+        assert!(
+            import
+                .unsupported
+                .iter()
+                .any(|line| line.starts_with("<home>/.local/share/example/saves: only applies"))
+        );
+    }
+
+    #[test]
+    fn convert_wildcards_to_include_globs() {
+        let manifest = fixture();
+        let home = tempfile::tempdir().unwrap();
+        use_home(home.path());
+        use_prefix("Example Remastered", Path::new("/nonexistent"));
+        let import = convert_game(
+            "Example Remastered",
+            &manifest["Example Game Remastered"],
+            GamePlatform::Windows,
+        );
+        assert!(import.unsupported.is_empty());
+        assert_eq!(
+            import.definition.paths,
+            vec![GameDefinitionPath {
+                include: vec!["/*.sav".into()],
+                ..GameDefinitionPath::new("<winDocuments>/My Games/Example Remastered".into())
+            }]
+        );
+    }
+}
@@ -8,10 +8,13 @@ use uuid::Uuid;
 
 use crate::{
     repository::{Repository, RepositoryExt},
-    utils::paths::data_dir,
+    utils::{device::device_name, files::write_file_atomic, paths::data_dir},
 };
 
-use super::definition::GameDefinition;
+use super::{
+    definition::GameDefinition,
+    history::{snapshot_exists, write_snapshot_manifest},
+};
 
 const MANIFEST_FILE: &str = "manifest.toml";
 
@@ -19,11 +22,36 @@ const MANIFEST_FILE: &str = "manifest.toml";
 pub struct GameSaveFileMetadata {
     pub modified: UtcDateTime,
     pub size: u64,
+    pub hash: Option<String>,
+}
+
+impl GameSaveFileMetadata {
+    pub fn same_content(&self, other: &GameSaveFileMetadata) -> bool {
+        match (&self.hash, &other.hash) {
+            (Some(hash), Some(other_hash)) => hash == other_hash && self.size == other.size,
+            // Manifests from before content hashing only have the file times
+            _ => self.modified == other.modified && self.size == other.size,
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Default, Debug)]
+pub enum GameSaveStorage {
+    // Every snapshot has its own directory of files
+    #[default]
+    Directory,
+    // Files are stored once per content hash
+    Blobs,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct GameSaveManifest {
     pub id: Uuid,
+    pub parent: Option<Uuid>,
+    pub device: Option<String>,
+    pub branch: Option<String>,
+    #[serde(default)]
+    pub storage: GameSaveStorage,
     pub definition: GameDefinition,
     pub timestamp: OffsetDateTime,
     pub files: HashMap<String, HashMap<RelativePathBuf, GameSaveFileMetadata>>,
@@ -45,8 +73,33 @@ pub fn read_repository_manifest(
     Ok(Some(manifest))
 }
 
+// How the current save in the repository came to change, recorded by repositories with history
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ManifestChange {
+    Stored,
+    Merged,
+    Restored,
+    Promoted,
+}
+
+fn change_message(manifest: &GameSaveManifest, change: ManifestChange) -> String {
+    let game = &manifest.definition.name;
+    let device = device_name();
+    match change {
+        ManifestChange::Stored => format!("Store {game} from {device}"),
+        ManifestChange::Merged => format!("Merge {game} from {device}"),
+        ManifestChange::Restored => {
+            format!("Restore {game} snapshot {} from {device}", manifest.id)
+        }
+        ManifestChange::Promoted => {
+            format!("Promote {game} snapshot {} from {device}", manifest.id)
+        }
+    }
+}
+
 pub fn write_repository_manifest(
     manifest: &GameSaveManifest,
+    change: ManifestChange,
     repository: &impl Repository,
 ) -> Result<()> {
     let path = RelativePath::new(&manifest.definition.name).join(MANIFEST_FILE);
@@ -59,16 +112,24 @@ pub fn write_repository_manifest(
         Some(_) => {}
         None => unreachable!("repository manifest file should always have a parent path"),
     }
+    // Repositories from before snapshot history only kept the current manifest
+    if let Some(previous) = read_repository_manifest(repository, &manifest.definition.name)?
+        && !snapshot_exists(repository, &previous.definition.name, previous.id)?
+    {
+        write_snapshot_manifest(&previous, repository)?;
+    }
+    write_snapshot_manifest(manifest, repository)?;
     let serialized = toml::to_string_pretty(manifest)
         .with_context(|| "failed to serialize repository manifest")?;
     repository
         .write_string(&path, &serialized)
         .with_context(|| "failed to write repository manifest")?;
+    repository.commit(&change_message(manifest, change))?;
     Ok(())
 }
 
 pub fn read_synced_manifest(game: &str) -> Result<Option<GameSaveManifest>> {
-    let path = data_dir()?.join(&game).join(MANIFEST_FILE);
+    let path = data_dir()?.join(game).join(MANIFEST_FILE);
     if !path.is_file() {
         return Ok(None);
     }
@@ -90,6 +151,15 @@ pub fn write_synced_manifest(manifest: &GameSaveManifest) -> Result<()> {
     }
     let serialized =
         toml::to_string_pretty(manifest).with_context(|| "failed to serialize synced manifest")?;
-    std::fs::write(path, serialized).with_context(|| "failed to write synced manifest")?;
+    write_file_atomic(&path, &mut serialized.as_bytes(), None)
+        .with_context(|| "failed to write synced manifest")?;
+    Ok(())
+}
+
+pub fn remove_synced_manifest(game: &str) -> Result<()> {
+    let path = data_dir()?.join(game).join(MANIFEST_FILE);
+    if path.is_file() {
+        std::fs::remove_file(path).with_context(|| "failed to remove synced manifest")?;
+    }
     Ok(())
 }
@@ -1,15 +1,31 @@
 use std::ffi::OsString;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use anyhow::{Error, Ok, Result};
 
+use super::{GamePlatform, definition::GameDefinition};
+use crate::utils::{config, paths::home_dir};
+
+// Stands for the Steam library a game is installed in, which differs between devices
+pub const STEAM_LIBRARY: &str = "<steamLibrary>";
+
 #[derive(PartialEq, Debug)]
 pub enum LinuxPathReplacement {
     Home,
+    XdgData,
+    XdgConfig,
+    XdgState,
+    XdgCache,
 }
 
-const LINUX_PATH_REPLACEMENTS: [LinuxPathReplacement; 1] = [LinuxPathReplacement::Home];
+const LINUX_PATH_REPLACEMENTS: [LinuxPathReplacement; 5] = [
+    LinuxPathReplacement::Home,
+    LinuxPathReplacement::XdgData,
+    LinuxPathReplacement::XdgConfig,
+    LinuxPathReplacement::XdgState,
+    LinuxPathReplacement::XdgCache,
+];
 
 impl FromStr for LinuxPathReplacement {
     type Err = ();
@@ -17,6 +33,10 @@ impl FromStr for LinuxPathReplacement {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "<home>" | "$HOME" | "~" => Result::Ok(LinuxPathReplacement::Home),
+            "<xdgData>" | "$XDG_DATA_HOME" => Result::Ok(LinuxPathReplacement::XdgData),
+            "<xdgConfig>" | "$XDG_CONFIG_HOME" => Result::Ok(LinuxPathReplacement::XdgConfig),
+            "<xdgState>" | "$XDG_STATE_HOME" => Result::Ok(LinuxPathReplacement::XdgState),
+            "<xdgCache>" | "$XDG_CACHE_HOME" => Result::Ok(LinuxPathReplacement::XdgCache),
             _ => Err(()),
         }
     }
@@ -25,42 +45,531 @@ impl FromStr for LinuxPathReplacement {
 impl LinuxPathReplacement {
     fn to_path(&self) -> Result<OsString> {
         match self {
-            LinuxPathReplacement::Home => Ok(std::env::home_dir()
-                .ok_or(Error::msg("failed to get home directory"))?
-                .into()),
+            LinuxPathReplacement::Home => Ok(home_dir()?.into()),
+            LinuxPathReplacement::XdgData => xdg_dir("XDG_DATA_HOME", &[".local", "share"]),
+            LinuxPathReplacement::XdgConfig => xdg_dir("XDG_CONFIG_HOME", &[".config"]),
+            LinuxPathReplacement::XdgState => xdg_dir("XDG_STATE_HOME", &[".local", "state"]),
+            LinuxPathReplacement::XdgCache => xdg_dir("XDG_CACHE_HOME", &[".cache"]),
+        }
+    }
+}
+
+fn xdg_dir(variable: &str, default: &[&str]) -> Result<OsString> {
+    resolve_xdg_dir(std::env::var_os(variable), default)
+}
+
+fn resolve_xdg_dir(value: Option<OsString>, default: &[&str]) -> Result<OsString> {
+    // The spec says relative paths in these variables are invalid and should be ignored
+    if let Some(path) = value
+        && Path::new(&path).is_absolute()
+    {
+        return Ok(path);
+    }
+    Ok(default
+        .iter()
+        .fold(home_dir()?, |path, segment| path.join(segment))
+        .into())
+}
+
+#[derive(PartialEq, Debug)]
+pub enum WindowsPathReplacement {
+    Home,
+    AppData,
+    LocalAppData,
+    LocalAppDataLow,
+    Documents,
+    Public,
+    ProgramData,
+}
+
+const WINDOWS_PATH_REPLACEMENTS: [WindowsPathReplacement; 7] = [
+    WindowsPathReplacement::Home,
+    WindowsPathReplacement::AppData,
+    WindowsPathReplacement::LocalAppData,
+    WindowsPathReplacement::LocalAppDataLow,
+    WindowsPathReplacement::Documents,
+    WindowsPathReplacement::Public,
+    WindowsPathReplacement::ProgramData,
+];
+
+impl FromStr for WindowsPathReplacement {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "<home>" | "%USERPROFILE%" | "~" => Result::Ok(WindowsPathReplacement::Home),
+            "<winAppData>" | "%APPDATA%" => Result::Ok(WindowsPathReplacement::AppData),
+            "<winLocalAppData>" | "%LOCALAPPDATA%" => {
+                Result::Ok(WindowsPathReplacement::LocalAppData)
+            }
+            "<winLocalAppDataLow>" => Result::Ok(WindowsPathReplacement::LocalAppDataLow),
+            "<winDocuments>" => Result::Ok(WindowsPathReplacement::Documents),
+            "<winPublic>" | "%PUBLIC%" => Result::Ok(WindowsPathReplacement::Public),
+            "<winProgramData>" | "%PROGRAMDATA%" => Result::Ok(WindowsPathReplacement::ProgramData),
+            _ => Err(()),
         }
     }
 }
 
-pub fn rewrite_path(path: &str) -> Result<PathBuf> {
+impl WindowsPathReplacement {
+    #[cfg(windows)]
+    fn to_path(&self, _definition: &GameDefinition) -> Result<OsString> {
+        let env = |name: &str| {
+            std::env::var_os(name).ok_or(Error::msg(format!("failed to get %{name}%")))
+        };
+        match self {
+            WindowsPathReplacement::Home => Ok(home_dir()?.into()),
+            WindowsPathReplacement::AppData => env("APPDATA"),
+            WindowsPathReplacement::LocalAppData => env("LOCALAPPDATA"),
+            WindowsPathReplacement::LocalAppDataLow => {
+                Ok(home_dir()?.join("AppData").join("LocalLow").into())
+            }
+            WindowsPathReplacement::Documents => Ok(home_dir()?.join("Documents").into()),
+            WindowsPathReplacement::Public => env("PUBLIC"),
+            WindowsPathReplacement::ProgramData => env("PROGRAMDATA"),
+        }
+    }
+
+    #[cfg(not(windows))]
+    fn to_path(&self, definition: &GameDefinition) -> Result<OsString> {
+        let drive = wine_prefix(definition)?.join("drive_c");
+        let user = drive.join("users").join("steamuser");
+        let path = match self {
+            WindowsPathReplacement::Home => user,
+            WindowsPathReplacement::AppData => user.join("AppData").join("Roaming"),
+            WindowsPathReplacement::LocalAppData => user.join("AppData").join("Local"),
+            WindowsPathReplacement::LocalAppDataLow => user.join("AppData").join("LocalLow"),
+            // Older prefixes still use the Windows XP folder name
+            WindowsPathReplacement::Documents if user.join("My Documents").is_dir() => {
+                user.join("My Documents")
+            }
+            WindowsPathReplacement::Documents => user.join("Documents"),
+            WindowsPathReplacement::Public => drive.join("users").join("Public"),
+            WindowsPathReplacement::ProgramData => drive.join("ProgramData"),
+        };
+        Ok(path.into())
+    }
+}
+
+pub fn steam_roots() -> Result<Vec<PathBuf>> {
+    let home = home_dir()?;
+    Ok(vec![
+        home.join(".steam").join("steam"),
+        home.join(".local").join("share").join("Steam"),
+        home.join(".var")
+            .join("app")
+            .join("com.valvesoftware.Steam")
+            .join(".local")
+            .join("share")
+            .join("Steam"),
+    ])
+}
+
+fn steam_libraries(steam_root: &Path) -> Vec<PathBuf> {
+    let mut libraries = vec![steam_root.to_path_buf()];
+    let folders = steam_root.join("steamapps").join("libraryfolders.vdf");
+    if let Result::Ok(file) = std::fs::read_to_string(folders) {
+        for line in file.lines() {
+            let mut values = line
+                .trim()
+                .split('"')
+                .filter(|value| !value.trim().is_empty());
+            if let (Some("path"), Some(path)) = (values.next(), values.next()) {
+                libraries.push(PathBuf::from(path.replace("\\\\", "\\")));
+            }
+        }
+    }
+    libraries
+}
+
+pub fn find_proton_prefix(steam_roots: &[PathBuf], steam_app_id: u64) -> Option<PathBuf> {
+    steam_roots
+        .iter()
+        .flat_map(|steam_root| steam_libraries(steam_root))
+        .map(|library| {
+            library
+                .join("steamapps")
+                .join("compatdata")
+                .join(steam_app_id.to_string())
+                .join("pfx")
+        })
+        .find(|prefix| prefix.is_dir())
+}
+
+pub fn find_steam_library(steam_roots: &[PathBuf], install_dir: &str) -> Option<PathBuf> {
+    steam_roots
+        .iter()
+        .flat_map(|steam_root| steam_libraries(steam_root))
+        .find(|library| {
+            library
+                .join("steamapps")
+                .join("common")
+                .join(install_dir)
+                .is_dir()
+        })
+}
+
+pub fn wine_prefix(definition: &GameDefinition) -> Result<PathBuf> {
+    if let Some(prefix) = config::load()?.wine_prefixes.get(&definition.name) {
+        return rewrite_linux_path(prefix);
+    }
+    match definition.steam_app_id {
+        Some(steam_app_id) => find_proton_prefix(&steam_roots()?, steam_app_id).ok_or(Error::msg(
+            format!("no Proton prefix found for Steam app {steam_app_id}"),
+        )),
+        None => Err(Error::msg(format!(
+            "{} needs a Steam app id or a Wine prefix set on this device to find Windows paths",
+            definition.name
+        ))),
+    }
+}
+
+fn replace_segments(
+    path: &str,
+    is_separator: impl Fn(char) -> bool,
+    replace: impl Fn(&str) -> Result<Option<OsString>>,
+) -> Result<PathBuf> {
     let mut rewritten = OsString::with_capacity(path.len());
     let mut first = true;
-    for segment in path.split(std::path::is_separator) {
+    for segment in path.split(is_separator) {
         if !first {
             rewritten.push(std::path::MAIN_SEPARATOR_STR);
         }
         first = false;
-        let replacement = match LinuxPathReplacement::from_str(segment) {
-            Result::Ok(replacement_type) => replacement_type.to_path()?,
-            Result::Err(_) => OsString::from(segment),
+        let replacement = match replace(segment)? {
+            Some(replacement) => replacement,
+            None => OsString::from(segment),
         };
         rewritten.push(replacement);
     }
-    let rewritten = PathBuf::from(rewritten);
+    Ok(PathBuf::from(rewritten))
+}
+
+fn rewrite_linux_path(path: &str) -> Result<PathBuf> {
+    replace_segments(
+        path,
+        std::path::is_separator,
+        |segment| match LinuxPathReplacement::from_str(segment) {
+            Result::Ok(replacement_type) => Ok(Some(replacement_type.to_path()?)),
+            Result::Err(_) => Ok(None),
+        },
+    )
+}
+
+// Paths into an install folder use the library it is in, anything else the main Steam folder
+fn rewrite_steam_library_path(path: &str) -> Result<PathBuf> {
+    let segments: Vec<&str> = path
+        .split(['/', '\\'])
+        .skip(1)
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    let steam_roots = steam_roots()?;
+    let library = match segments.as_slice() {
+        [] => return Err(Error::msg(format!("path {} is too broad", path))),
+        ["steamapps", "common", install_dir, ..] => find_steam_library(&steam_roots, install_dir)
+            .ok_or(Error::msg(format!(
+            "{install_dir} is not installed in a Steam library"
+        )))?,
+        _ => steam_roots
+            .into_iter()
+            .find(|steam_root| steam_root.is_dir())
+            .ok_or(Error::msg("no Steam install found"))?,
+    };
+    Ok(segments
+        .iter()
+        .fold(library, |path, segment| path.join(segment)))
+}
+
+pub fn rewrite_path(path: &str, definition: &GameDefinition) -> Result<PathBuf> {
+    if path.split(['/', '\\']).next() == Some(STEAM_LIBRARY) {
+        return rewrite_steam_library_path(path);
+    }
+    let (rewritten, roots) = match definition.platform {
+        GamePlatform::Linux => {
+            let rewritten = rewrite_linux_path(path)?;
+            let roots = LINUX_PATH_REPLACEMENTS
+                .iter()
+                .filter_map(|replacement_type| replacement_type.to_path().ok())
+                .collect::<Vec<_>>();
+            (rewritten, roots)
+        }
+        GamePlatform::Windows => {
+            // Windows paths are accepted with either separator on every platform
+            let is_separator = |c| c == '/' || c == '\\';
+            let rewritten = replace_segments(path, is_separator, |segment| {
+                match WindowsPathReplacement::from_str(segment) {
+                    Result::Ok(replacement_type) => Ok(Some(replacement_type.to_path(definition)?)),
+                    Result::Err(_) => Ok(None),
+                }
+            })?;
+            let roots = WINDOWS_PATH_REPLACEMENTS
+                .iter()
+                .filter_map(|replacement_type| replacement_type.to_path(definition).ok())
+                .collect::<Vec<_>>();
+            (rewritten, roots)
+        }
+    };
     if !rewritten.is_absolute() {
         return Err(Error::msg(format!(
             "path {} is not absolute",
             rewritten.display()
         )));
     }
-    for replacement_type in LINUX_PATH_REPLACEMENTS {
-        let replacement = match replacement_type.to_path() {
-            Result::Ok(replacement) => PathBuf::from(replacement),
-            Result::Err(_) => continue,
-        };
-        if replacement.starts_with(&rewritten) {
+    for replacement in roots {
+        if PathBuf::from(replacement).starts_with(&rewritten) {
             return Err(Error::msg(format!("path {} is too broad", path)));
         }
     }
     Ok(rewritten)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::paths::{DirOverrides, override_dirs};
+
+    const STEAM_APP_ID: u64 = 123450;
+    const XDG_DEFAULTS: [(&str, &[&str]); 4] = [
+        ("<xdgData>", &[".local", "share"]),
+        ("<xdgConfig>", &[".config"]),
+        ("<xdgState>", &[".local", "state"]),
+        ("<xdgCache>", &[".cache"]),
+    ];
+
+    fn use_home(home: &Path) {
+        override_dirs(Some(DirOverrides {
+            home: home.into(),
+            config: home.join("config"),
+            data: home.join("data"),
+        }));
+    }
+
+    fn linux_game() -> GameDefinition {
+        GameDefinition {
+            name: "Example".into(),
+            platform: GamePlatform::Linux,
+            paths: vec![],
+            steam_app_id: None,
+            conflict_policy: None,
+        }
+    }
+
+    fn windows_game() -> GameDefinition {
+        GameDefinition {
+            name: "Example".into(),
+            platform: GamePlatform::Windows,
+            paths: vec![],
+            steam_app_id: Some(STEAM_APP_ID),
+            conflict_policy: None,
+        }
+    }
+
+    // A Steam install with the game's Proton prefix in a second library
+    fn steam_with_prefix(home: &Path, library: &Path) -> PathBuf {
+        let steam_root = home.join(".steam").join("steam");
+        std::fs::create_dir_all(steam_root.join("steamapps")).unwrap();
+        std::fs::write(
+            steam_root.join("steamapps").join("libraryfolders.vdf"),
+            format!(
+                "\"libraryfolders\"\n{{\n\t\"1\"\n\t{{\n\t\t\"path\"\t\t\"{}\"\n\t}}\n}}\n",
+                library.display()
+            ),
+        )
+        .unwrap();
+        let prefix = library
+            .join("steamapps")
+            .join("compatdata")
+            .join(STEAM_APP_ID.to_string())
+            .join("pfx");
+        std::fs::create_dir_all(&prefix).unwrap();
+        prefix
+    }
+
+    #[test]
+    fn windows_paths_resolve_in_proton_prefix() {
+        let home = tempfile::tempdir().unwrap();
+        let library = tempfile::tempdir().unwrap();
+        use_home(home.path());
+        let prefix = steam_with_prefix(home.path(), library.path());
+        assert_eq!(
+            find_proton_prefix(&steam_roots().unwrap(), STEAM_APP_ID),
+            Some(prefix.clone())
+        );
+        let drive = prefix.join("drive_c");
+        let user = drive.join("users").join("steamuser");
+        let definition = windows_game();
+        for (placeholder, expected) in [
+            ("<home>", user.clone()),
+            ("<winAppData>", user.join("AppData").join("Roaming")),
+            ("<winLocalAppData>", user.join("AppData").join("Local")),
+            (
+                "<winLocalAppDataLow>",
+                user.join("AppData").join("LocalLow"),
+            ),
+            ("<winDocuments>", user.join("Documents")),
+            ("<winPublic>", drive.join("users").join("Public")),
+            ("<winProgramData>", drive.join("ProgramData")),
+        ] {
+            assert_eq!(
+                rewrite_path(&format!("{placeholder}\\Example\\saves"), &definition).unwrap(),
+                expected.join("Example").join("saves"),
+                "{placeholder}"
+            );
+        }
+    }
+
+    #[test]
+    fn windows_documents_falls_back_to_my_documents() {
+        let home = tempfile::tempdir().unwrap();
+        let library = tempfile::tempdir().unwrap();
+        use_home(home.path());
+        let prefix = steam_with_prefix(home.path(), library.path());
+        let my_documents = prefix.join("drive_c/users/steamuser/My Documents");
+        std::fs::create_dir_all(&my_documents).unwrap();
+        assert_eq!(
+            rewrite_path("<winDocuments>/My Games/Example", &windows_game()).unwrap(),
+            my_documents.join("My Games").join("Example")
+        );
+    }
+
+    #[test]
+    fn windows_paths_must_be_below_a_placeholder() {
+        let home = tempfile::tempdir().unwrap();
+        let library = tempfile::tempdir().unwrap();
+        use_home(home.path());
+        let prefix = steam_with_prefix(home.path(), library.path());
+        let definition = windows_game();
+        for path in [
+            "<home>",
+            "<winAppData>",
+            "<winLocalAppData>",
+            "<winLocalAppDataLow>",
+            "<winDocuments>/",
+            "<winPublic>",
+            "<winProgramData>",
+        ] {
+            let error = rewrite_path(path, &definition).err().unwrap();
+            assert!(error.to_string().contains("too broad"), "{path}: {error}");
+        }
+        // Folders holding one of the locations are rejected too
+        let users = prefix.join("drive_c").join("users");
+        let error = rewrite_path(&users.to_string_lossy(), &definition)
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("too broad"));
+        rewrite_path("<winAppData>/Example", &definition).unwrap();
+    }
+
+    #[test]
+    fn windows_paths_need_a_prefix() {
+        let home = tempfile::tempdir().unwrap();
+        use_home(home.path());
+        let error = rewrite_path("<winAppData>/Example", &windows_game())
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("no Proton prefix found"));
+    }
+
+    #[test]
+    fn windows_paths_use_the_wine_prefix_set_on_this_device() {
+        let home = tempfile::tempdir().unwrap();
+        let library = tempfile::tempdir().unwrap();
+        use_home(home.path());
+        steam_with_prefix(home.path(), library.path());
+        config::save(&config::Config {
+            wine_prefixes: [("Example".into(), "<home>/Games/example".into())].into(),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(
+            rewrite_path("<winAppData>/Example", &windows_game()).unwrap(),
+            home.path()
+                .join("Games/example/drive_c/users/steamuser/AppData/Roaming/Example")
+        );
+    }
+
+    #[test]
+    fn steam_library_paths_resolve_in_the_library_with_the_install() {
+        let home = tempfile::tempdir().unwrap();
+        let library = tempfile::tempdir().unwrap();
+        use_home(home.path());
+        steam_with_prefix(home.path(), library.path());
+        let steam_root = home.path().join(".steam").join("steam");
+        let install = library.path().join("steamapps/common/Example Game");
+        std::fs::create_dir_all(&install).unwrap();
+        for definition in [linux_game(), windows_game()] {
+            assert_eq!(
+                rewrite_path(
+                    "<steamLibrary>/steamapps/common/Example Game/saves",
+                    &definition
+                )
+                .unwrap(),
+                install.join("saves")
+            );
+            assert_eq!(
+                rewrite_path("<steamLibrary>\\userdata", &definition).unwrap(),
+                steam_root.join("userdata")
+            );
+            assert!(rewrite_path("<steamLibrary>", &definition).is_err());
+            assert!(
+                rewrite_path("<steamLibrary>/steamapps/common/Missing/saves", &definition).is_err()
+            );
+        }
+    }
+
+    #[test]
+    fn xdg_variables_take_precedence() {
+        let home = tempfile::tempdir().unwrap();
+        use_home(home.path());
+        let data = home.path().join("elsewhere");
+        assert_eq!(
+            resolve_xdg_dir(Some(data.clone().into()), &[".local", "share"]).unwrap(),
+            data.into_os_string()
+        );
+    }
+
+    #[test]
+    fn relative_xdg_variables_are_ignored() {
+        let home = tempfile::tempdir().unwrap();
+        use_home(home.path());
+        for value in ["", "relative/data", "./data"] {
+            assert_eq!(
+                resolve_xdg_dir(Some(value.into()), &[".local", "share"]).unwrap(),
+                home.path().join(".local").join("share").into_os_string(),
+                "{value:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn xdg_defaults_follow_the_spec() {
+        let home = tempfile::tempdir().unwrap();
+        use_home(home.path());
+        for (_, default) in XDG_DEFAULTS {
+            assert_eq!(
+                resolve_xdg_dir(None, default).unwrap(),
+                default
+                    .iter()
+                    .fold(home.path().to_path_buf(), |path, segment| path
+                        .join(segment))
+                    .into_os_string()
+            );
+        }
+    }
+
+    #[test]
+    fn linux_paths_must_be_below_a_placeholder() {
+        let home = tempfile::tempdir().unwrap();
+        use_home(home.path());
+        let definition = linux_game();
+        for (placeholder, _) in XDG_DEFAULTS {
+            for path in [placeholder.to_string(), format!("{placeholder}/")] {
+                let error = rewrite_path(&path, &definition).err().unwrap();
+                assert!(error.to_string().contains("too broad"), "{path}: {error}");
+            }
+            rewrite_path(&format!("{placeholder}/example"), &definition).unwrap();
+        }
+        let error = rewrite_path("<home>", &definition).err().unwrap();
+        assert!(error.to_string().contains("too broad"));
+    }
+}
@@ -0,0 +1,4 @@
+pub mod games;
+pub mod repository;
+pub mod sync;
+pub mod utils;
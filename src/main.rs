@@ -2,9 +2,6 @@ use anyhow::Result;
 use clap::Parser;
 
 mod commands;
-mod games;
-mod repository;
-mod utils;
 
 use commands::Commands;
 
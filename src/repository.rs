@@ -1,12 +1,19 @@
-use std::io::Write;
-
 use anyhow::{Context, Error, Result};
 use relative_path::{RelativePath, RelativePathBuf};
 
 pub mod any_repository;
+pub mod archive_repository;
+pub mod git_repository;
 pub mod local_repository;
+pub mod lock;
+pub mod memory_repository;
+pub mod s3_repository;
+pub mod settings;
+pub mod sftp_repository;
+pub mod webdav_repository;
 
 pub use any_repository::{AnyRepository, AnyRepositoryConfig};
+use settings::SETTINGS_FILE;
 
 pub trait Repository {
     fn is_file(&self, path: &RelativePath) -> Result<bool>;
@@ -14,10 +21,26 @@ pub trait Repository {
     fn read_dir(
         &self,
         path: &RelativePath,
-    ) -> Result<impl Iterator<Item = Result<RelativePathBuf>>>;
-    fn read_file(&self, path: &RelativePath) -> Result<impl std::io::Read>;
-    fn write_file(&self, path: &RelativePath) -> Result<impl std::io::Write>;
+    ) -> Result<impl Iterator<Item = Result<RelativePathBuf>> + use<Self>>;
+    fn read_file(&self, path: &RelativePath) -> Result<impl std::io::Read + use<Self>>;
+    fn write_file(&self, path: &RelativePath, content: &mut impl std::io::Read) -> Result<()>;
+    // Only writes the file if nothing is there yet, returning false if something was
+    fn create_file(&self, path: &RelativePath, content: &mut impl std::io::Read) -> Result<bool>;
     fn remove(&self, path: &RelativePath) -> Result<()>;
+
+    // Repositories that keep history record the changes written so far
+    fn commit(&self, _message: &str) -> Result<()> {
+        Ok(())
+    }
+
+    // Repositories with a remote copy catch up before a sync and publish after it
+    fn pull(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn push(&self) -> Result<()> {
+        Ok(())
+    }
 }
 
 pub trait RepositoryExt {
@@ -32,8 +55,7 @@ impl<T: Repository> RepositoryExt for T {
     }
 
     fn write_string(&self, path: &RelativePath, content: &str) -> Result<()> {
-        let mut file = self.write_file(path)?;
-        file.write_all(content.as_ref())
+        self.write_file(path, &mut content.as_bytes())
             .with_context(|| format!("failed to write {path}"))
     }
 }
@@ -43,6 +65,21 @@ fn open_repository(config: &AnyRepositoryConfig) -> Result<AnyRepository> {
         AnyRepositoryConfig::Local(local_config) => {
             AnyRepository::Local(local_repository::open_repository(local_config)?)
         }
+        AnyRepositoryConfig::WebDav(webdav_config) => {
+            AnyRepository::WebDav(webdav_repository::open_repository(webdav_config)?)
+        }
+        AnyRepositoryConfig::S3(s3_config) => {
+            AnyRepository::S3(s3_repository::open_repository(s3_config)?)
+        }
+        AnyRepositoryConfig::Sftp(sftp_config) => {
+            AnyRepository::Sftp(sftp_repository::open_repository(sftp_config)?)
+        }
+        AnyRepositoryConfig::Git(git_config) => {
+            AnyRepository::Git(git_repository::open_repository(git_config)?)
+        }
+        AnyRepositoryConfig::Archive(archive_config) => {
+            AnyRepository::Archive(archive_repository::open_repository(archive_config)?)
+        }
     };
     Ok(repository)
 }
@@ -50,7 +87,7 @@ fn open_repository(config: &AnyRepositoryConfig) -> Result<AnyRepository> {
 pub fn get_repository(config: &Option<AnyRepositoryConfig>) -> Result<AnyRepository> {
     let config = config.as_ref().ok_or(Error::msg("Repository is not set"))?;
     let repository = open_repository(config)?;
-    if !repository.is_file(RelativePath::new("GameSaveSync.toml"))? {
+    if !repository.is_file(RelativePath::new(SETTINGS_FILE))? {
         return Result::Err(Error::msg(format!(
             "Repository {} has not been correctly initialized",
             config
@@ -62,13 +99,15 @@ pub fn get_repository(config: &Option<AnyRepositoryConfig>) -> Result<AnyReposit
 pub fn prepare_repository(config: &Option<AnyRepositoryConfig>) -> Result<()> {
     let config = config.as_ref().ok_or(Error::msg("Repository is not set"))?;
     let repository = open_repository(config)?;
-    if !repository.is_file(RelativePath::new("GameSaveSync.toml"))? {
+    if !repository.is_file(RelativePath::new(SETTINGS_FILE))? {
         if repository.read_dir(RelativePath::new(""))?.next().is_some() {
             return Result::Err(Error::msg(format!("Repository {} should be empty", config)));
         }
         repository
-            .write_string(RelativePath::new("GameSaveSync.toml"), "")
+            .write_string(RelativePath::new(SETTINGS_FILE), "")
             .with_context(|| "failed to create repository")?;
+        repository.commit("Create repository")?;
+        repository.push()?;
     }
     Ok(())
 }
@@ -6,12 +6,22 @@ use serde::{Deserialize, Serialize};
 #[serde(tag = "type")]
 pub enum AnyRepositoryConfig {
     Local(super::local_repository::LocalRepositoryConfig),
+    WebDav(super::webdav_repository::WebDavRepositoryConfig),
+    S3(super::s3_repository::S3RepositoryConfig),
+    Sftp(super::sftp_repository::SftpRepositoryConfig),
+    Git(super::git_repository::GitRepositoryConfig),
+    Archive(super::archive_repository::ArchiveRepositoryConfig),
 }
 
 impl std::fmt::Display for AnyRepositoryConfig {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             AnyRepositoryConfig::Local(config) => write!(f, "{}", config),
+            AnyRepositoryConfig::WebDav(config) => write!(f, "{}", config),
+            AnyRepositoryConfig::S3(config) => write!(f, "{}", config),
+            AnyRepositoryConfig::Sftp(config) => write!(f, "{}", config),
+            AnyRepositoryConfig::Git(config) => write!(f, "{}", config),
+            AnyRepositoryConfig::Archive(config) => write!(f, "{}", config),
         }
     }
 }
@@ -19,45 +29,138 @@ impl std::fmt::Display for AnyRepositoryConfig {
 #[derive(Debug)]
 pub enum AnyRepository {
     Local(super::local_repository::LocalRepository),
+    Memory(super::memory_repository::MemoryRepository),
+    WebDav(super::webdav_repository::WebDavRepository),
+    S3(super::s3_repository::S3Repository),
+    Sftp(super::sftp_repository::SftpRepository),
+    Git(super::git_repository::GitRepository),
+    Archive(super::archive_repository::ArchiveRepository),
 }
 
 impl super::Repository for AnyRepository {
     fn is_file(&self, path: &RelativePath) -> Result<bool> {
         match self {
             AnyRepository::Local(repository) => repository.is_file(path),
+            AnyRepository::Memory(repository) => repository.is_file(path),
+            AnyRepository::WebDav(repository) => repository.is_file(path),
+            AnyRepository::S3(repository) => repository.is_file(path),
+            AnyRepository::Sftp(repository) => repository.is_file(path),
+            AnyRepository::Git(repository) => repository.is_file(path),
+            AnyRepository::Archive(repository) => repository.is_file(path),
         }
     }
 
     fn is_dir(&self, path: &RelativePath) -> Result<bool> {
         match self {
             AnyRepository::Local(repository) => repository.is_dir(path),
+            AnyRepository::Memory(repository) => repository.is_dir(path),
+            AnyRepository::WebDav(repository) => repository.is_dir(path),
+            AnyRepository::S3(repository) => repository.is_dir(path),
+            AnyRepository::Sftp(repository) => repository.is_dir(path),
+            AnyRepository::Git(repository) => repository.is_dir(path),
+            AnyRepository::Archive(repository) => repository.is_dir(path),
         }
     }
 
     fn read_dir(
         &self,
         path: &RelativePath,
-    ) -> Result<impl Iterator<Item = Result<RelativePathBuf>>> {
-        match self {
-            AnyRepository::Local(repository) => repository.read_dir(path),
-        }
+    ) -> Result<impl Iterator<Item = Result<RelativePathBuf>> + use<>> {
+        // Each backend has its own iterator type, so they share a boxed one
+        let entries: Box<dyn Iterator<Item = Result<RelativePathBuf>> + 'static> = match self {
+            AnyRepository::Local(repository) => Box::new(repository.read_dir(path)?),
+            AnyRepository::Memory(repository) => Box::new(repository.read_dir(path)?),
+            AnyRepository::WebDav(repository) => Box::new(repository.read_dir(path)?),
+            AnyRepository::S3(repository) => Box::new(repository.read_dir(path)?),
+            AnyRepository::Sftp(repository) => Box::new(repository.read_dir(path)?),
+            AnyRepository::Git(repository) => Box::new(repository.read_dir(path)?),
+            AnyRepository::Archive(repository) => Box::new(repository.read_dir(path)?),
+        };
+        Ok(entries)
+    }
+
+    fn read_file(&self, path: &RelativePath) -> Result<impl std::io::Read + use<>> {
+        let file: Box<dyn std::io::Read + 'static> = match self {
+            AnyRepository::Local(repository) => Box::new(repository.read_file(path)?),
+            AnyRepository::Memory(repository) => Box::new(repository.read_file(path)?),
+            AnyRepository::WebDav(repository) => Box::new(repository.read_file(path)?),
+            AnyRepository::S3(repository) => Box::new(repository.read_file(path)?),
+            AnyRepository::Sftp(repository) => Box::new(repository.read_file(path)?),
+            AnyRepository::Git(repository) => Box::new(repository.read_file(path)?),
+            AnyRepository::Archive(repository) => Box::new(repository.read_file(path)?),
+        };
+        Ok(file)
     }
 
-    fn read_file(&self, path: &RelativePath) -> Result<impl std::io::Read> {
+    fn write_file(&self, path: &RelativePath, content: &mut impl std::io::Read) -> Result<()> {
         match self {
-            AnyRepository::Local(repository) => repository.read_file(path),
+            AnyRepository::Local(repository) => repository.write_file(path, content),
+            AnyRepository::Memory(repository) => repository.write_file(path, content),
+            AnyRepository::WebDav(repository) => repository.write_file(path, content),
+            AnyRepository::S3(repository) => repository.write_file(path, content),
+            AnyRepository::Sftp(repository) => repository.write_file(path, content),
+            AnyRepository::Git(repository) => repository.write_file(path, content),
+            AnyRepository::Archive(repository) => repository.write_file(path, content),
         }
     }
 
-    fn write_file(&self, path: &RelativePath) -> Result<impl std::io::Write> {
+    fn create_file(&self, path: &RelativePath, content: &mut impl std::io::Read) -> Result<bool> {
         match self {
-            AnyRepository::Local(repository) => repository.write_file(path),
+            AnyRepository::Local(repository) => repository.create_file(path, content),
+            AnyRepository::Memory(repository) => repository.create_file(path, content),
+            AnyRepository::WebDav(repository) => repository.create_file(path, content),
+            AnyRepository::S3(repository) => repository.create_file(path, content),
+            AnyRepository::Sftp(repository) => repository.create_file(path, content),
+            AnyRepository::Git(repository) => repository.create_file(path, content),
+            AnyRepository::Archive(repository) => repository.create_file(path, content),
         }
     }
 
     fn remove(&self, path: &RelativePath) -> Result<()> {
         match self {
             AnyRepository::Local(repository) => repository.remove(path),
+            AnyRepository::Memory(repository) => repository.remove(path),
+            AnyRepository::WebDav(repository) => repository.remove(path),
+            AnyRepository::S3(repository) => repository.remove(path),
+            AnyRepository::Sftp(repository) => repository.remove(path),
+            AnyRepository::Git(repository) => repository.remove(path),
+            AnyRepository::Archive(repository) => repository.remove(path),
+        }
+    }
+
+    fn commit(&self, message: &str) -> Result<()> {
+        match self {
+            AnyRepository::Local(repository) => repository.commit(message),
+            AnyRepository::Memory(repository) => repository.commit(message),
+            AnyRepository::WebDav(repository) => repository.commit(message),
+            AnyRepository::S3(repository) => repository.commit(message),
+            AnyRepository::Sftp(repository) => repository.commit(message),
+            AnyRepository::Git(repository) => repository.commit(message),
+            AnyRepository::Archive(repository) => repository.commit(message),
+        }
+    }
+
+    fn pull(&self) -> Result<()> {
+        match self {
+            AnyRepository::Local(repository) => repository.pull(),
+            AnyRepository::Memory(repository) => repository.pull(),
+            AnyRepository::WebDav(repository) => repository.pull(),
+            AnyRepository::S3(repository) => repository.pull(),
+            AnyRepository::Sftp(repository) => repository.pull(),
+            AnyRepository::Git(repository) => repository.pull(),
+            AnyRepository::Archive(repository) => repository.pull(),
+        }
+    }
+
+    fn push(&self) -> Result<()> {
+        match self {
+            AnyRepository::Local(repository) => repository.push(),
+            AnyRepository::Memory(repository) => repository.push(),
+            AnyRepository::WebDav(repository) => repository.push(),
+            AnyRepository::S3(repository) => repository.push(),
+            AnyRepository::Sftp(repository) => repository.push(),
+            AnyRepository::Git(repository) => repository.push(),
+            AnyRepository::Archive(repository) => repository.push(),
         }
     }
 }
@@ -0,0 +1,221 @@
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Error, Result};
+use relative_path::{RelativePath, RelativePathBuf};
+use serde::{Deserialize, Serialize};
+
+use super::{Repository, memory_repository::MemoryRepository};
+use crate::utils::files::write_file_atomic;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ArchiveRepositoryConfig {
+    pub path: PathBuf,
+}
+
+impl std::fmt::Display for ArchiveRepositoryConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.path.display())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+}
+
+impl ArchiveFormat {
+    pub fn from_path(path: &Path) -> Option<ArchiveFormat> {
+        let extension = path.extension()?.to_string_lossy().to_lowercase();
+        match extension.as_str() {
+            "zip" => Some(ArchiveFormat::Zip),
+            "tar" => Some(ArchiveFormat::Tar),
+            _ => None,
+        }
+    }
+}
+
+// Works on the archive contents in memory, and rewrites the whole archive when pushed
+#[derive(Debug)]
+pub struct ArchiveRepository {
+    path: PathBuf,
+    format: ArchiveFormat,
+    files: MemoryRepository,
+    changed: Cell<bool>,
+}
+
+type ArchiveFiles = BTreeMap<RelativePathBuf, Vec<u8>>;
+
+fn read_zip(content: &[u8]) -> Result<ArchiveFiles> {
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(content))?;
+    let mut files = BTreeMap::new();
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index)?;
+        if file.is_dir() {
+            continue;
+        }
+        let path = RelativePathBuf::from(file.name()).normalize();
+        let mut buffer = vec![];
+        file.read_to_end(&mut buffer)
+            .with_context(|| format!("failed to read {path}"))?;
+        files.insert(path, buffer);
+    }
+    Ok(files)
+}
+
+fn write_zip(files: &ArchiveFiles) -> Result<Vec<u8>> {
+    let mut archive = zip::ZipWriter::new(std::io::Cursor::new(vec![]));
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .large_file(
+            files
+                .values()
+                .any(|content| content.len() >= u32::MAX as usize),
+        );
+    for (path, content) in files {
+        archive.start_file(path.as_str(), options)?;
+        archive.write_all(content)?;
+    }
+    Ok(archive.finish()?.into_inner())
+}
+
+fn read_tar(content: &[u8]) -> Result<ArchiveFiles> {
+    let mut archive = tar::Archive::new(content);
+    let mut files = BTreeMap::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = RelativePathBuf::from(entry.path()?.to_string_lossy().as_ref()).normalize();
+        let mut buffer = vec![];
+        entry
+            .read_to_end(&mut buffer)
+            .with_context(|| format!("failed to read {path}"))?;
+        files.insert(path, buffer);
+    }
+    Ok(files)
+}
+
+fn write_tar(files: &ArchiveFiles) -> Result<Vec<u8>> {
+    let mut archive = tar::Builder::new(vec![]);
+    for (path, content) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_secs()),
+        );
+        archive.append_data(&mut header, path.as_str(), content.as_slice())?;
+    }
+    Ok(archive.into_inner()?)
+}
+
+fn write_archive(path: &Path, format: ArchiveFormat, files: &ArchiveFiles) -> Result<()> {
+    let content = match format {
+        ArchiveFormat::Zip => write_zip(files),
+        ArchiveFormat::Tar => write_tar(files),
+    }
+    .with_context(|| format!("failed to build archive {}", path.display()))?;
+    write_file_atomic(path, &mut content.as_slice(), None)
+}
+
+impl ArchiveRepository {
+    // Writes the archive if anything changed, changes that are never written are discarded
+    pub fn close(&self) -> Result<()> {
+        if !self.changed.get() {
+            return Ok(());
+        }
+        write_archive(&self.path, self.format, &self.files.files())?;
+        self.changed.set(false);
+        Ok(())
+    }
+}
+
+impl Repository for ArchiveRepository {
+    fn is_file(&self, path: &RelativePath) -> Result<bool> {
+        self.files.is_file(path)
+    }
+
+    fn is_dir(&self, path: &RelativePath) -> Result<bool> {
+        self.files.is_dir(path)
+    }
+
+    fn read_dir(
+        &self,
+        path: &RelativePath,
+    ) -> Result<impl Iterator<Item = Result<RelativePathBuf>> + use<>> {
+        self.files.read_dir(path)
+    }
+
+    fn read_file(&self, path: &RelativePath) -> Result<impl std::io::Read + use<>> {
+        self.files.read_file(path)
+    }
+
+    fn write_file(&self, path: &RelativePath, content: &mut impl std::io::Read) -> Result<()> {
+        self.files.write_file(path, content)?;
+        self.changed.set(true);
+        Ok(())
+    }
+
+    fn create_file(&self, path: &RelativePath, content: &mut impl std::io::Read) -> Result<bool> {
+        let created = self.files.create_file(path, content)?;
+        if created {
+            self.changed.set(true);
+        }
+        Ok(created)
+    }
+
+    fn remove(&self, path: &RelativePath) -> Result<()> {
+        self.files.remove(path)?;
+        self.changed.set(true);
+        Ok(())
+    }
+
+    fn push(&self) -> Result<()> {
+        self.close()
+    }
+}
+
+fn archive_format(path: &Path) -> Result<ArchiveFormat> {
+    ArchiveFormat::from_path(path).ok_or(Error::msg(format!(
+        "{} should be a .zip or .tar archive",
+        path.display()
+    )))
+}
+
+pub fn create_archive(path: &Path) -> Result<()> {
+    let format = archive_format(path)?;
+    if path.exists() {
+        return Ok(());
+    }
+    write_archive(path, format, &BTreeMap::new())
+}
+
+pub fn open_repository(config: &ArchiveRepositoryConfig) -> Result<ArchiveRepository> {
+    let format = archive_format(&config.path)?;
+    if !config.path.is_file() {
+        return Result::Err(Error::msg(format!(
+            "Archive {} does not exist",
+            config.path.display()
+        )));
+    }
+    let content = std::fs::read(&config.path)
+        .with_context(|| format!("failed to read {}", config.path.display()))?;
+    let files = match format {
+        ArchiveFormat::Zip => read_zip(&content),
+        ArchiveFormat::Tar => read_tar(&content),
+    }
+    .with_context(|| format!("failed to read archive {}", config.path.display()))?;
+    Ok(ArchiveRepository {
+        path: config.path.clone(),
+        format,
+        files: MemoryRepository::from_files(files),
+        changed: Cell::new(false),
+    })
+}
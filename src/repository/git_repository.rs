@@ -0,0 +1,264 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use anyhow::{Context, Error, Result};
+use relative_path::{RelativePath, RelativePathBuf};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    Repository,
+    local_repository::{self, LocalRepository, LocalRepositoryConfig},
+};
+use crate::utils::{device::device_name, files::TEMP_SUFFIX};
+
+const DEFAULT_REMOTE: &str = "origin";
+const GIT_DIR: &str = ".git";
+const GITIGNORE_FILE: &str = ".gitignore";
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GitRepositoryConfig {
+    pub path: PathBuf,
+    // Changes are only committed locally when there is no remote
+    pub remote: Option<String>,
+}
+
+impl std::fmt::Display for GitRepositoryConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} (git", self.path.display())?;
+        if let Some(remote) = &self.remote {
+            write!(f, ", syncing with {remote}")?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[derive(Debug)]
+pub struct GitRepository {
+    local: LocalRepository,
+    path: PathBuf,
+    remote: Option<String>,
+    // Identity to commit with when git has none configured
+    identity: Vec<String>,
+}
+
+fn run_git(path: &Path, args: &[&str]) -> Result<Output> {
+    Command::new("git")
+        .arg("-C")
+        .arg(path)
+        .args(args)
+        .output()
+        .with_context(|| "failed to run git, is it installed?")
+}
+
+fn git(path: &Path, args: &[&str]) -> Result<String> {
+    let output = run_git(path, args)?;
+    if !output.status.success() {
+        return Err(Error::msg(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn git_succeeds(path: &Path, args: &[&str]) -> Result<bool> {
+    Ok(run_git(path, args)?.status.success())
+}
+
+impl GitRepository {
+    fn git(&self, args: &[&str]) -> Result<String> {
+        let args: Vec<&str> = self
+            .identity
+            .iter()
+            .map(String::as_str)
+            .chain(args.iter().copied())
+            .collect();
+        git(&self.path, &args)
+    }
+
+    fn has_commits(&self) -> Result<bool> {
+        git_succeeds(&self.path, &["rev-parse", "--verify", "--quiet", "HEAD"])
+    }
+
+    // Catch files written outside of a manifest change, such as new game definitions
+    fn commit_pending(&self) -> Result<()> {
+        self.commit(&format!("Update repository from {}", device_name()))
+    }
+
+    fn rebase_onto_remote(&self, remote: &str) -> Result<()> {
+        self.git(&["fetch", "--quiet", remote])?;
+        let branch = self.git(&["symbolic-ref", "--short", "HEAD"])?;
+        let upstream = format!("{remote}/{branch}");
+        let upstream_ref = format!("refs/remotes/{upstream}");
+        if !git_succeeds(
+            &self.path,
+            &["rev-parse", "--verify", "--quiet", &upstream_ref],
+        )? {
+            // Nothing has been pushed to the remote yet
+            return Ok(());
+        }
+        if !self.has_commits()? {
+            self.git(&["reset", "--quiet", "--hard", &upstream])?;
+            return Ok(());
+        }
+        if let Err(error) = self.git(&["rebase", "--quiet", &upstream]) {
+            let _ = self.git(&["rebase", "--abort"]);
+            return Err(error.context(format!(
+                "changes in {} conflict with {remote}, resolve them with git",
+                self.path.display()
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Repository for GitRepository {
+    fn is_file(&self, path: &RelativePath) -> Result<bool> {
+        self.local.is_file(path)
+    }
+
+    fn is_dir(&self, path: &RelativePath) -> Result<bool> {
+        self.local.is_dir(path)
+    }
+
+    fn read_dir(
+        &self,
+        path: &RelativePath,
+    ) -> Result<impl Iterator<Item = Result<RelativePathBuf>> + use<>> {
+        let is_root = path.normalize().as_str().is_empty();
+        // Git's own files are not part of the repository
+        Ok(self.local.read_dir(path)?.filter(move |entry| {
+            !is_root
+                || entry
+                    .as_ref()
+                    .map_or(true, |entry| entry != GIT_DIR && entry != GITIGNORE_FILE)
+        }))
+    }
+
+    fn read_file(&self, path: &RelativePath) -> Result<impl std::io::Read + use<>> {
+        self.local.read_file(path)
+    }
+
+    fn write_file(&self, path: &RelativePath, content: &mut impl std::io::Read) -> Result<()> {
+        self.local.write_file(path, content)
+    }
+
+    fn create_file(&self, path: &RelativePath, content: &mut impl std::io::Read) -> Result<bool> {
+        self.local.create_file(path, content)
+    }
+
+    fn remove(&self, path: &RelativePath) -> Result<()> {
+        self.local.remove(path)
+    }
+
+    fn commit(&self, message: &str) -> Result<()> {
+        self.git(&["add", "--all"])?;
+        if git_succeeds(&self.path, &["diff", "--cached", "--quiet"])? {
+            return Ok(());
+        }
+        self.git(&["commit", "--quiet", "--message", message])?;
+        Ok(())
+    }
+
+    fn pull(&self) -> Result<()> {
+        self.commit_pending()?;
+        match &self.remote {
+            Some(remote) => self.rebase_onto_remote(remote),
+            None => Ok(()),
+        }
+    }
+
+    fn push(&self) -> Result<()> {
+        self.commit_pending()?;
+        let Some(remote) = &self.remote else {
+            return Ok(());
+        };
+        if !self.has_commits()? {
+            return Ok(());
+        }
+        let push = ["push", "--quiet", "--set-upstream", remote, "HEAD"];
+        if self.git(&push).is_err() {
+            // Another device pushed while this one was syncing
+            self.rebase_onto_remote(remote)?;
+            self.git(&push)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_gitignore(path: &Path) -> Result<()> {
+    let gitignore = path.join(GITIGNORE_FILE);
+    if !gitignore.exists() {
+        std::fs::write(&gitignore, format!("lock.toml\n*{TEMP_SUFFIX}\n"))
+            .with_context(|| format!("failed to write {}", gitignore.display()))?;
+    }
+    Ok(())
+}
+
+// Sets up a working tree at path, cloning the remote when there is nothing there yet
+pub fn init_repository(path: &Path, remote_url: Option<&str>) -> Result<GitRepositoryConfig> {
+    let is_empty = !path.exists()
+        || path
+            .read_dir()
+            .with_context(|| format!("failed to enumerate {}", path.display()))?
+            .next()
+            .is_none();
+    if !path.join(GIT_DIR).exists() {
+        match remote_url {
+            Some(url) if is_empty => {
+                let parent = path.parent().unwrap_or(Path::new("."));
+                std::fs::create_dir_all(parent)?;
+                git(parent, &["clone", "--quiet", url, &path.to_string_lossy()])?;
+            }
+            _ => {
+                std::fs::create_dir_all(path)?;
+                git(path, &["init", "--quiet"])?;
+            }
+        }
+    }
+    let has_remote = git_succeeds(path, &["remote", "get-url", DEFAULT_REMOTE])?;
+    match remote_url {
+        Some(url) if !has_remote => {
+            git(path, &["remote", "add", DEFAULT_REMOTE, url])?;
+        }
+        _ => {}
+    }
+    write_gitignore(path)?;
+    Ok(GitRepositoryConfig {
+        path: path.to_path_buf(),
+        remote: (has_remote || remote_url.is_some()).then(|| DEFAULT_REMOTE.into()),
+    })
+}
+
+pub fn open_repository(config: &GitRepositoryConfig) -> Result<GitRepository> {
+    let local = local_repository::open_repository(&LocalRepositoryConfig {
+        path: config.path.clone(),
+    })?;
+    if !config.path.join(GIT_DIR).exists() {
+        return Result::Err(Error::msg(format!(
+            "Path {} is not a git working tree",
+            config.path.display()
+        )));
+    }
+    if let Some(remote) = &config.remote {
+        git(&config.path, &["remote", "get-url", remote])
+            .with_context(|| format!("git remote {remote} is not set up"))?;
+    }
+    let mut identity = vec![];
+    for (key, value) in [
+        ("user.name", "GameSaveSync".to_string()),
+        ("user.email", format!("gamesavesync@{}", device_name())),
+    ] {
+        if !git_succeeds(&config.path, &["config", "--get", key])? {
+            identity.push("-c".into());
+            identity.push(format!("{key}={value}"));
+        }
+    }
+    Ok(GitRepository {
+        local,
+        path: config.path.clone(),
+        remote: config.remote.clone(),
+        identity,
+    })
+}
@@ -5,6 +5,7 @@ use relative_path::{PathExt, RelativePath, RelativePathBuf};
 use serde::{Deserialize, Serialize};
 
 use super::Repository;
+use crate::utils::files::write_file_atomic;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct LocalRepositoryConfig {
@@ -36,7 +37,7 @@ impl Repository for LocalRepository {
     fn read_dir(
         &self,
         path: &RelativePath,
-    ) -> Result<impl Iterator<Item = Result<RelativePathBuf>>> {
+    ) -> Result<impl Iterator<Item = Result<RelativePathBuf>> + use<>> {
         let path = path.to_path(&self.path);
         Ok(path
             .read_dir()
@@ -53,18 +54,40 @@ impl Repository for LocalRepository {
             }))
     }
 
-    fn read_file(&self, path: &RelativePath) -> Result<impl std::io::Read> {
+    fn read_file(&self, path: &RelativePath) -> Result<impl std::io::Read + use<>> {
         let path = path.to_path(&self.path);
         std::fs::File::open(&path).with_context(|| format!("failed to read {}", path.display()))
     }
 
-    fn write_file(&self, path: &RelativePath) -> Result<impl std::io::Write> {
+    fn write_file(&self, path: &RelativePath, content: &mut impl std::io::Read) -> Result<()> {
         let path = path.to_path(&self.path);
-        match path.parent() {
-            Some(parent) => std::fs::create_dir_all(parent)?,
-            None => {}
+        write_file_atomic(&path, content, None)
+    }
+
+    fn create_file(&self, path: &RelativePath, content: &mut impl std::io::Read) -> Result<bool> {
+        let path = path.to_path(&self.path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
         }
-        std::fs::File::create(&path).with_context(|| format!("failed to read {}", path.display()))
+        let mut file = match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(file) => file,
+            Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => return Ok(false),
+            Err(error) => {
+                return Err(
+                    Error::new(error).context(format!("failed to create {}", path.display()))
+                );
+            }
+        };
+        std::io::copy(content, &mut file)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        file.sync_all()
+            .with_context(|| format!("failed to sync {}", path.display()))?;
+        Ok(true)
     }
 
     fn remove(&self, path: &RelativePath) -> Result<()> {
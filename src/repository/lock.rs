@@ -0,0 +1,237 @@
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Error, Result};
+use relative_path::{RelativePath, RelativePathBuf};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use super::{Repository, RepositoryExt};
+use crate::utils::device::device_name;
+
+const LOCK_FILE: &str = "lock.toml";
+const STALE_LOCK_AGE: time::Duration = time::Duration::minutes(10);
+// Well within the stale age, so a slow sync is never mistaken for an abandoned one
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LockHolder {
+    pub token: Uuid,
+    pub device: String,
+    pub pid: u32,
+    pub timestamp: OffsetDateTime,
+}
+
+#[derive(Debug)]
+pub enum LockEvent<'a> {
+    Waiting(&'a LockHolder),
+    Breaking(&'a LockHolder),
+}
+
+impl LockHolder {
+    fn new(token: Uuid) -> Self {
+        LockHolder {
+            token,
+            device: device_name(),
+            pid: std::process::id(),
+            timestamp: OffsetDateTime::now_utc(),
+        }
+    }
+
+    fn is_stale(&self) -> bool {
+        if OffsetDateTime::now_utc() - self.timestamp > STALE_LOCK_AGE {
+            return true;
+        }
+        self.device == device_name() && !process_exists(self.pid)
+    }
+
+    fn serialize(&self) -> Result<String> {
+        toml::to_string_pretty(self).with_context(|| "failed to serialize lock")
+    }
+}
+
+impl std::fmt::Display for LockHolder {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (process {}) since {}",
+            self.device, self.pid, self.timestamp
+        )
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn process_exists(pid: u32) -> bool {
+    std::path::Path::new("/proc").join(pid.to_string()).exists()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn process_exists(_pid: u32) -> bool {
+    true
+}
+
+// Also works as the repository while it is held, refreshing the lock between operations
+// so that long transfers are not mistaken for an abandoned sync
+pub struct RepositoryLock<'a, R: Repository> {
+    repository: &'a R,
+    path: RelativePathBuf,
+    token: Uuid,
+    refreshed: Cell<Instant>,
+}
+
+impl<R: Repository> RepositoryLock<'_, R> {
+    pub fn refresh(&self) -> Result<()> {
+        match read_holder(self.repository, &self.path)? {
+            Some(holder) if holder.token == self.token => {}
+            Some(holder) => {
+                return Err(Error::msg(format!(
+                    "lock {} was taken over by {holder}",
+                    self.path
+                )));
+            }
+            None => {
+                return Err(Error::msg(format!("lock {} was removed", self.path)));
+            }
+        }
+        self.repository
+            .write_string(&self.path, &LockHolder::new(self.token).serialize()?)
+            .with_context(|| format!("failed to refresh lock {}", self.path))?;
+        self.refreshed.set(Instant::now());
+        Ok(())
+    }
+
+    fn keep_alive(&self) -> Result<()> {
+        if self.refreshed.get().elapsed() < REFRESH_INTERVAL {
+            return Ok(());
+        }
+        self.refresh()
+    }
+}
+
+impl<R: Repository> Drop for RepositoryLock<'_, R> {
+    fn drop(&mut self) {
+        // Leave the lock alone if another device broke it and took over,
+        // a lock that fails to be removed will be detected as stale later
+        let _ = remove_if_held_by(self.repository, &self.path, self.token);
+    }
+}
+
+impl<'a, R: Repository> Repository for RepositoryLock<'a, R> {
+    fn is_file(&self, path: &RelativePath) -> Result<bool> {
+        self.keep_alive()?;
+        self.repository.is_file(path)
+    }
+
+    fn is_dir(&self, path: &RelativePath) -> Result<bool> {
+        self.keep_alive()?;
+        self.repository.is_dir(path)
+    }
+
+    fn read_dir(
+        &self,
+        path: &RelativePath,
+    ) -> Result<impl Iterator<Item = Result<RelativePathBuf>> + use<'a, R>> {
+        self.keep_alive()?;
+        self.repository.read_dir(path)
+    }
+
+    fn read_file(&self, path: &RelativePath) -> Result<impl std::io::Read + use<'a, R>> {
+        self.keep_alive()?;
+        self.repository.read_file(path)
+    }
+
+    fn write_file(&self, path: &RelativePath, content: &mut impl std::io::Read) -> Result<()> {
+        self.keep_alive()?;
+        self.repository.write_file(path, content)
+    }
+
+    fn create_file(&self, path: &RelativePath, content: &mut impl std::io::Read) -> Result<bool> {
+        self.keep_alive()?;
+        self.repository.create_file(path, content)
+    }
+
+    fn remove(&self, path: &RelativePath) -> Result<()> {
+        self.keep_alive()?;
+        self.repository.remove(path)
+    }
+
+    fn commit(&self, message: &str) -> Result<()> {
+        self.repository.commit(message)
+    }
+
+    fn pull(&self) -> Result<()> {
+        self.repository.pull()
+    }
+
+    fn push(&self) -> Result<()> {
+        self.repository.push()
+    }
+}
+
+fn read_holder(repository: &impl Repository, path: &RelativePath) -> Result<Option<LockHolder>> {
+    if !repository.is_file(path)? {
+        return Ok(None);
+    }
+    let file = repository
+        .read_string(path)
+        .with_context(|| format!("failed to read lock {path}"))?;
+    let holder = toml::from_str(&file).with_context(|| format!("failed to parse lock {path}"))?;
+    Ok(Some(holder))
+}
+
+fn remove_if_held_by(repository: &impl Repository, path: &RelativePath, token: Uuid) -> Result<()> {
+    match read_holder(repository, path)? {
+        Some(holder) if holder.token == token => repository.remove(path),
+        _ => Ok(()),
+    }
+}
+
+pub fn acquire_lock<'a, R: Repository>(
+    repository: &'a R,
+    dir: &RelativePath,
+    timeout: Duration,
+    break_lock: bool,
+    on_event: &mut dyn FnMut(LockEvent),
+) -> Result<RepositoryLock<'a, R>> {
+    let path = dir.join(LOCK_FILE);
+    let token = Uuid::new_v4();
+    let mut waited = Duration::ZERO;
+    let mut break_lock = break_lock;
+    loop {
+        let serialized = LockHolder::new(token).serialize()?;
+        if repository
+            .create_file(&path, &mut serialized.as_bytes())
+            .with_context(|| format!("failed to write lock {path}"))?
+        {
+            return Ok(RepositoryLock {
+                repository,
+                path,
+                token,
+                refreshed: Cell::new(Instant::now()),
+            });
+        }
+        let Some(holder) = read_holder(repository, &path)? else {
+            // Released before it could be read
+            continue;
+        };
+        if break_lock || holder.is_stale() {
+            on_event(LockEvent::Breaking(&holder));
+            // Only the lock that was found is removed, if another device broke it first
+            // and took over, creating the lock fails again on the next attempt
+            remove_if_held_by(repository, &path, holder.token)?;
+            break_lock = false;
+        } else if waited >= timeout {
+            return Err(Error::msg(format!(
+                "{dir} is locked by {holder}, use --break-lock if that sync is no longer running"
+            )));
+        } else {
+            if waited.is_zero() {
+                on_event(LockEvent::Waiting(&holder));
+            }
+            std::thread::sleep(POLL_INTERVAL);
+            waited += POLL_INTERVAL;
+        }
+    }
+}
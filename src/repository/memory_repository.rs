@@ -0,0 +1,97 @@
+use std::cell::{Ref, RefCell};
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{Context, Error, Result};
+use relative_path::{RelativePath, RelativePathBuf};
+
+use super::Repository;
+
+// Keeps every file in memory, for tests and for trying things out without touching disk
+#[derive(Default, Debug)]
+pub struct MemoryRepository {
+    files: RefCell<BTreeMap<RelativePathBuf, Vec<u8>>>,
+}
+
+impl MemoryRepository {
+    pub fn new() -> Self {
+        MemoryRepository::default()
+    }
+
+    pub fn from_files(files: BTreeMap<RelativePathBuf, Vec<u8>>) -> Self {
+        MemoryRepository {
+            files: RefCell::new(files),
+        }
+    }
+
+    pub fn files(&self) -> Ref<'_, BTreeMap<RelativePathBuf, Vec<u8>>> {
+        self.files.borrow()
+    }
+
+    fn children(&self, path: &RelativePath) -> BTreeSet<RelativePathBuf> {
+        self.files
+            .borrow()
+            .keys()
+            .filter_map(|file| file.strip_prefix(path).ok())
+            .filter_map(|child| child.components().next())
+            .map(|child| RelativePathBuf::from(child.as_str()))
+            .collect()
+    }
+}
+
+impl Repository for MemoryRepository {
+    fn is_file(&self, path: &RelativePath) -> Result<bool> {
+        Ok(self.files.borrow().contains_key(&path.normalize()))
+    }
+
+    fn is_dir(&self, path: &RelativePath) -> Result<bool> {
+        let path = path.normalize();
+        Ok(path.as_str().is_empty() || !self.children(&path).is_empty())
+    }
+
+    fn read_dir(
+        &self,
+        path: &RelativePath,
+    ) -> Result<impl Iterator<Item = Result<RelativePathBuf>> + use<>> {
+        let path = path.normalize();
+        let children = self.children(&path);
+        if children.is_empty() && !path.as_str().is_empty() {
+            return Err(Error::msg(format!("failed to enumerate {path}")));
+        }
+        Ok(children.into_iter().map(Ok))
+    }
+
+    fn read_file(&self, path: &RelativePath) -> Result<impl std::io::Read + use<>> {
+        let content = self
+            .files
+            .borrow()
+            .get(&path.normalize())
+            .cloned()
+            .ok_or(Error::msg(format!("failed to read {path}")))?;
+        Ok(std::io::Cursor::new(content))
+    }
+
+    fn write_file(&self, path: &RelativePath, content: &mut impl std::io::Read) -> Result<()> {
+        let mut buffer = vec![];
+        content
+            .read_to_end(&mut buffer)
+            .with_context(|| format!("failed to write {path}"))?;
+        self.files.borrow_mut().insert(path.normalize(), buffer);
+        Ok(())
+    }
+
+    fn create_file(&self, path: &RelativePath, content: &mut impl std::io::Read) -> Result<bool> {
+        if self.is_file(path)? {
+            return Ok(false);
+        }
+        self.write_file(path, content)?;
+        Ok(true)
+    }
+
+    fn remove(&self, path: &RelativePath) -> Result<()> {
+        let path = path.normalize();
+        self.files
+            .borrow_mut()
+            .retain(|file, _| file.strip_prefix(&path).is_err());
+        Ok(())
+    }
+}
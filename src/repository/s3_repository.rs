@@ -0,0 +1,535 @@
+use std::io::Read;
+
+use anyhow::{Context, Error, Result};
+use hmac::{Hmac, Mac};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+use relative_path::{RelativePath, RelativePathBuf};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use ureq::{
+    Agent, Body,
+    http::{Method, Request, Response, StatusCode},
+};
+
+use super::Repository;
+
+const DEFAULT_REGION: &str = "us-east-1";
+// S3 needs every part but the last to be at least 5 MiB
+const PART_SIZE: usize = 8 * 1024 * 1024;
+const UNRESERVED: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+fn default_region() -> String {
+    DEFAULT_REGION.into()
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct S3RepositoryConfig {
+    pub endpoint: String,
+    pub bucket: String,
+    #[serde(default = "default_region")]
+    pub region: String,
+    #[serde(default)]
+    pub prefix: String,
+    // Taken from AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY when not set, the secret is never saved
+    pub access_key_id: Option<String>,
+    #[serde(default, skip_serializing)]
+    pub secret_access_key: Option<String>,
+}
+
+impl std::fmt::Display for S3RepositoryConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "s3://{}", self.bucket)?;
+        if !self.prefix.is_empty() {
+            write!(f, "/{}", self.prefix.trim_matches('/'))?;
+        }
+        write!(f, " at {}", self.endpoint)
+    }
+}
+
+pub struct S3Repository {
+    agent: Agent,
+    endpoint: String,
+    host: String,
+    bucket: String,
+    region: String,
+    prefix: String,
+    access_key_id: String,
+    secret_access_key: String,
+}
+
+impl std::fmt::Debug for S3Repository {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("S3Repository")
+            .field("endpoint", &self.endpoint)
+            .field("bucket", &self.bucket)
+            .field("prefix", &self.prefix)
+            .finish()
+    }
+}
+
+fn sha256_hex(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+fn hmac_sha256(key: &[u8], content: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(content.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn encode(value: &str) -> String {
+    utf8_percent_encode(value, UNRESERVED).to_string()
+}
+
+fn xml_values(xml: &str, path: &[&str]) -> Result<Vec<String>> {
+    let document =
+        roxmltree::Document::parse(xml).with_context(|| "failed to parse S3 response")?;
+    let Some((last, parents)) = path.split_last() else {
+        return Ok(vec![]);
+    };
+    Ok(document
+        .descendants()
+        .filter(|node| node.tag_name().name() == *last)
+        .filter(|node| {
+            let ancestors = node.ancestors().skip(1).map(|node| node.tag_name().name());
+            parents
+                .iter()
+                .rev()
+                .zip(ancestors)
+                .all(|(expected, name)| *expected == name)
+        })
+        .filter_map(|node| node.text().map(str::to_string))
+        .collect())
+}
+
+struct Listing {
+    keys: Vec<String>,
+    prefixes: Vec<String>,
+}
+
+impl S3Repository {
+    fn key(&self, path: &RelativePath) -> String {
+        let path = path.normalize();
+        match (self.prefix.is_empty(), path.as_str().is_empty()) {
+            (true, _) => path.to_string(),
+            (false, true) => self.prefix.clone(),
+            (false, false) => format!("{}/{}", self.prefix, path),
+        }
+    }
+
+    fn dir_prefix(&self, path: &RelativePath) -> String {
+        let key = self.key(path);
+        if key.is_empty() {
+            key
+        } else {
+            format!("{key}/")
+        }
+    }
+
+    fn request(
+        &self,
+        method: Method,
+        key: &str,
+        query: &[(&str, &str)],
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> Result<Response<Body>> {
+        let now = time::OffsetDateTime::now_utc();
+        let date = format!(
+            "{:04}{:02}{:02}",
+            now.year(),
+            u8::from(now.month()),
+            now.day()
+        );
+        let timestamp = format!(
+            "{date}T{:02}{:02}{:02}Z",
+            now.hour(),
+            now.minute(),
+            now.second()
+        );
+        let payload_hash = sha256_hex(body);
+        let mut uri = format!("/{}", encode(&self.bucket));
+        if !key.is_empty() {
+            uri.push('/');
+            uri.push_str(&key.split('/').map(encode).collect::<Vec<_>>().join("/"));
+        }
+        let mut query: Vec<(String, String)> = query
+            .iter()
+            .map(|(name, value)| (encode(name), encode(value)))
+            .collect();
+        query.sort();
+        let query = query
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join("&");
+        // Every header sent is signed, in lowercase and sorted by name
+        let mut signed_headers: Vec<(String, String)> = [
+            ("host", self.host.as_str()),
+            ("x-amz-content-sha256", payload_hash.as_str()),
+            ("x-amz-date", timestamp.as_str()),
+        ]
+        .iter()
+        .chain(headers)
+        .map(|(name, value)| (name.to_lowercase(), value.trim().to_string()))
+        .collect();
+        signed_headers.sort();
+        let canonical_headers: String = signed_headers
+            .iter()
+            .map(|(name, value)| format!("{name}:{value}\n"))
+            .collect();
+        let header_names = signed_headers
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>()
+            .join(";");
+        let canonical_request = format!(
+            "{method}\n{uri}\n{query}\n{canonical_headers}\n{header_names}\n{payload_hash}"
+        );
+        let scope = format!("{date}/{}/s3/aws4_request", self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{timestamp}\n{scope}\n{}",
+            sha256_hex(canonical_request.as_bytes())
+        );
+        let signing_key = ["s3", "aws4_request"].iter().fold(
+            hmac_sha256(
+                &hmac_sha256(format!("AWS4{}", self.secret_access_key).as_bytes(), &date),
+                &self.region,
+            ),
+            |key, part| hmac_sha256(&key, part),
+        );
+        let signature: String = hmac_sha256(&signing_key, &string_to_sign)
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={header_names}, Signature={signature}",
+            self.access_key_id
+        );
+        let url = if query.is_empty() {
+            format!("{}{uri}", self.endpoint)
+        } else {
+            format!("{}{uri}?{query}", self.endpoint)
+        };
+        let mut request = Request::builder()
+            .method(method.clone())
+            .uri(&url)
+            .header("Authorization", authorization)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("x-amz-date", timestamp);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let request = request
+            .body(body)
+            .with_context(|| format!("failed to build S3 {method} request for {key}"))?;
+        let response = self
+            .agent
+            .run(request)
+            .with_context(|| format!("S3 {method} request for {key} failed"))?;
+        if response.status() == StatusCode::UNAUTHORIZED
+            || response.status() == StatusCode::FORBIDDEN
+        {
+            return Err(Error::msg(format!(
+                "S3 refused access to {} ({})",
+                self.bucket,
+                response.status()
+            )));
+        }
+        Ok(response)
+    }
+
+    fn check(response: Response<Body>, action: &str) -> Result<Response<Body>> {
+        if response.status().is_success() {
+            return Ok(response);
+        }
+        let status = response.status();
+        let message = response
+            .into_body()
+            .read_to_string()
+            .ok()
+            .and_then(|xml| xml_values(&xml, &["Error", "Message"]).ok())
+            .and_then(|messages| messages.into_iter().next())
+            .unwrap_or_default();
+        Err(Error::msg(format!(
+            "failed to {action} ({status}) {message}"
+        )))
+    }
+
+    fn list(&self, prefix: &str, delimiter: bool, max_keys: Option<usize>) -> Result<Listing> {
+        let mut listing = Listing {
+            keys: vec![],
+            prefixes: vec![],
+        };
+        let mut token: Option<String> = None;
+        loop {
+            let max_keys = max_keys.map(|max_keys| max_keys.to_string());
+            let mut query = vec![("list-type", "2"), ("prefix", prefix)];
+            if delimiter {
+                query.push(("delimiter", "/"));
+            }
+            if let Some(max_keys) = &max_keys {
+                query.push(("max-keys", max_keys));
+            }
+            if let Some(token) = &token {
+                query.push(("continuation-token", token.as_str()));
+            }
+            let response = Self::check(
+                self.request(Method::GET, "", &query, &[], &[])?,
+                &format!("list {prefix}"),
+            )?;
+            let xml = response
+                .into_body()
+                .read_to_string()
+                .with_context(|| format!("failed to read listing of {prefix}"))?;
+            listing
+                .keys
+                .extend(xml_values(&xml, &["ListBucketResult", "Contents", "Key"])?);
+            listing.prefixes.extend(xml_values(
+                &xml,
+                &["ListBucketResult", "CommonPrefixes", "Prefix"],
+            )?);
+            let truncated = xml_values(&xml, &["ListBucketResult", "IsTruncated"])?;
+            token = xml_values(&xml, &["ListBucketResult", "NextContinuationToken"])?
+                .into_iter()
+                .next();
+            if max_keys.is_some() || truncated.first().map(String::as_str) != Some("true") {
+                return Ok(listing);
+            }
+            if token.is_none() {
+                return Err(Error::msg(format!(
+                    "S3 listing of {prefix} was truncated without a continuation token"
+                )));
+            }
+        }
+    }
+
+    fn upload_multipart(
+        &self,
+        key: &str,
+        first_part: Vec<u8>,
+        content: &mut impl Read,
+    ) -> Result<()> {
+        let response = Self::check(
+            self.request(Method::POST, key, &[("uploads", "")], &[], &[])?,
+            &format!("start upload of {key}"),
+        )?;
+        let xml = response.into_body().read_to_string()?;
+        let upload_id = xml_values(&xml, &["InitiateMultipartUploadResult", "UploadId"])?
+            .into_iter()
+            .next()
+            .ok_or(Error::msg(format!("S3 did not start an upload of {key}")))?;
+        let result = self.upload_parts(key, &upload_id, first_part, content);
+        if result.is_err() {
+            // Abandoned parts are kept and billed until the upload is aborted
+            let _ = self.request(Method::DELETE, key, &[("uploadId", &upload_id)], &[], &[]);
+        }
+        result
+    }
+
+    fn upload_parts(
+        &self,
+        key: &str,
+        upload_id: &str,
+        first_part: Vec<u8>,
+        content: &mut impl Read,
+    ) -> Result<()> {
+        let mut parts = vec![];
+        let mut part = first_part;
+        while !part.is_empty() {
+            let number = (parts.len() + 1).to_string();
+            let response = Self::check(
+                self.request(
+                    Method::PUT,
+                    key,
+                    &[("partNumber", &number), ("uploadId", upload_id)],
+                    &[],
+                    &part,
+                )?,
+                &format!("upload part {number} of {key}"),
+            )?;
+            let etag = response
+                .headers()
+                .get("ETag")
+                .and_then(|etag| etag.to_str().ok())
+                .ok_or(Error::msg(format!(
+                    "S3 returned no ETag for part {number} of {key}"
+                )))?
+                .to_string();
+            parts.push(format!(
+                "<Part><PartNumber>{number}</PartNumber><ETag>{etag}</ETag></Part>"
+            ));
+            part = read_part(content)?;
+        }
+        let body = format!(
+            "<CompleteMultipartUpload>{}</CompleteMultipartUpload>",
+            parts.concat()
+        );
+        let response = Self::check(
+            self.request(
+                Method::POST,
+                key,
+                &[("uploadId", upload_id)],
+                &[],
+                body.as_bytes(),
+            )?,
+            &format!("finish upload of {key}"),
+        )?;
+        // Completing can still fail after the response has started, reported in the body
+        let xml = response.into_body().read_to_string()?;
+        if let Some(message) = xml_values(&xml, &["Error", "Message"])?.into_iter().next() {
+            return Err(Error::msg(format!(
+                "failed to finish upload of {key}: {message}"
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn read_part(content: &mut impl Read) -> Result<Vec<u8>> {
+    let mut part = Vec::with_capacity(PART_SIZE);
+    content
+        .take(PART_SIZE as u64)
+        .read_to_end(&mut part)
+        .with_context(|| "failed to read upload content")?;
+    Ok(part)
+}
+
+impl Repository for S3Repository {
+    fn is_file(&self, path: &RelativePath) -> Result<bool> {
+        let key = self.key(path);
+        let response = self.request(Method::HEAD, &key, &[], &[], &[])?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(false),
+            _ => Self::check(response, &format!("check {key}")).map(|_| true),
+        }
+    }
+
+    fn is_dir(&self, path: &RelativePath) -> Result<bool> {
+        if path.normalize().as_str().is_empty() {
+            return Ok(true);
+        }
+        let listing = self.list(&self.dir_prefix(path), false, Some(1))?;
+        Ok(!listing.keys.is_empty())
+    }
+
+    fn read_dir(
+        &self,
+        path: &RelativePath,
+    ) -> Result<impl Iterator<Item = Result<RelativePathBuf>> + use<>> {
+        let prefix = self.dir_prefix(path);
+        let listing = self.list(&prefix, true, None)?;
+        if listing.keys.is_empty()
+            && listing.prefixes.is_empty()
+            && !path.normalize().as_str().is_empty()
+        {
+            return Err(Error::msg(format!("failed to enumerate {path}")));
+        }
+        let children: Vec<RelativePathBuf> = listing
+            .keys
+            .iter()
+            .chain(listing.prefixes.iter())
+            .filter_map(|key| key.strip_prefix(&prefix))
+            .map(|name| name.trim_end_matches('/'))
+            .filter(|name| !name.is_empty())
+            .map(RelativePathBuf::from)
+            .collect();
+        Ok(children.into_iter().map(Ok))
+    }
+
+    fn read_file(&self, path: &RelativePath) -> Result<impl std::io::Read + use<>> {
+        let key = self.key(path);
+        let response = Self::check(
+            self.request(Method::GET, &key, &[], &[], &[])?,
+            &format!("read {path}"),
+        )?;
+        Ok(response.into_body().into_reader())
+    }
+
+    fn write_file(&self, path: &RelativePath, content: &mut impl std::io::Read) -> Result<()> {
+        let key = self.key(path);
+        let first_part = read_part(content)?;
+        if first_part.len() < PART_SIZE {
+            Self::check(
+                self.request(Method::PUT, &key, &[], &[], &first_part)?,
+                &format!("write {path}"),
+            )?;
+            return Ok(());
+        }
+        self.upload_multipart(&key, first_part, content)
+    }
+
+    fn create_file(&self, path: &RelativePath, content: &mut impl std::io::Read) -> Result<bool> {
+        let key = self.key(path);
+        let mut body = vec![];
+        content
+            .read_to_end(&mut body)
+            .with_context(|| format!("failed to read content for {path}"))?;
+        let response = self.request(Method::PUT, &key, &[], &[("If-None-Match", "*")], &body)?;
+        match response.status() {
+            // Conflict is returned when another conditional write of the key is in progress
+            StatusCode::PRECONDITION_FAILED | StatusCode::CONFLICT => Ok(false),
+            _ => Self::check(response, &format!("create {path}")).map(|_| true),
+        }
+    }
+
+    fn remove(&self, path: &RelativePath) -> Result<()> {
+        // Directories are only key prefixes, so every object under one is deleted
+        let mut keys = self.list(&self.dir_prefix(path), false, None)?.keys;
+        if !path.normalize().as_str().is_empty() {
+            keys.push(self.key(path));
+        }
+        for key in keys {
+            let response = self.request(Method::DELETE, &key, &[], &[], &[])?;
+            if response.status() != StatusCode::NOT_FOUND {
+                Self::check(response, &format!("remove {key}"))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn open_repository(config: &S3RepositoryConfig) -> Result<S3Repository> {
+    let endpoint = config.endpoint.trim_end_matches('/');
+    let host = match endpoint
+        .strip_prefix("https://")
+        .or(endpoint.strip_prefix("http://"))
+    {
+        Some(host) if !host.is_empty() && !host.contains('/') => host,
+        _ => {
+            return Result::Err(Error::msg(format!(
+                "endpoint {} should be an http or https URL without a path",
+                config.endpoint
+            )));
+        }
+    };
+    let credential = |value: &Option<String>, variable: &str| {
+        value
+            .clone()
+            .or_else(|| std::env::var(variable).ok())
+            .ok_or(Error::msg(format!(
+                "no S3 credentials configured or set in {variable}"
+            )))
+    };
+    let agent: Agent = Agent::config_builder()
+        .http_status_as_error(false)
+        .build()
+        .into();
+    let repository = S3Repository {
+        agent,
+        endpoint: endpoint.into(),
+        host: host.into(),
+        bucket: config.bucket.clone(),
+        region: config.region.clone(),
+        prefix: config.prefix.trim_matches('/').into(),
+        access_key_id: credential(&config.access_key_id, "AWS_ACCESS_KEY_ID")?,
+        secret_access_key: credential(&config.secret_access_key, "AWS_SECRET_ACCESS_KEY")?,
+    };
+    // Listing fails early if the bucket or credentials are wrong
+    repository.list(&repository.dir_prefix(RelativePath::new("")), true, Some(1))?;
+    Ok(repository)
+}
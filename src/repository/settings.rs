@@ -0,0 +1,45 @@
+use anyhow::{Context, Result};
+use relative_path::RelativePath;
+use serde::{Deserialize, Serialize};
+
+use super::{Repository, RepositoryExt};
+
+pub const SETTINGS_FILE: &str = "GameSaveSync.toml";
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct RetentionPolicy {
+    pub keep_last: Option<usize>,
+    pub keep_daily: Option<usize>,
+    pub keep_weekly: Option<usize>,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        RetentionPolicy {
+            keep_last: Some(10),
+            keep_daily: None,
+            keep_weekly: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct RepositorySettings {
+    #[serde(default)]
+    pub retention: RetentionPolicy,
+}
+
+pub fn read_settings(repository: &impl Repository) -> Result<RepositorySettings> {
+    let file = repository
+        .read_string(RelativePath::new(SETTINGS_FILE))
+        .with_context(|| "failed to read repository settings")?;
+    toml::from_str(&file).with_context(|| "failed to parse repository settings")
+}
+
+pub fn write_settings(repository: &impl Repository, settings: &RepositorySettings) -> Result<()> {
+    let serialized = toml::to_string_pretty(settings)
+        .with_context(|| "failed to serialize repository settings")?;
+    repository
+        .write_string(RelativePath::new(SETTINGS_FILE), &serialized)
+        .with_context(|| "failed to write repository settings")
+}
@@ -0,0 +1,396 @@
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Error, Result};
+use relative_path::{RelativePath, RelativePathBuf};
+use serde::{Deserialize, Serialize};
+use ssh2::{
+    CheckResult, ErrorCode, KnownHostFileKind, OpenFlags, OpenType, RenameFlags, Session, Sftp,
+};
+
+use super::Repository;
+use crate::utils::{files::TEMP_SUFFIX, paths::home_dir};
+
+const DEFAULT_PORT: u16 = 22;
+const BACKUP_SUFFIX: &str = ".gamesavesync-old";
+// LIBSSH2_FX_NO_SUCH_FILE
+const NO_SUCH_FILE: i32 = 2;
+
+fn default_port() -> u16 {
+    DEFAULT_PORT
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SftpRepositoryConfig {
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    pub user: String,
+    pub path: String,
+    // The SSH agent is used when not set
+    pub key_file: Option<PathBuf>,
+    // ~/.ssh/known_hosts is used when not set
+    pub known_hosts: Option<PathBuf>,
+}
+
+impl std::fmt::Display for SftpRepositoryConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "sftp://{}@{}", self.user, self.host)?;
+        if self.port != DEFAULT_PORT {
+            write!(f, ":{}", self.port)?;
+        }
+        if !self.path.starts_with('/') {
+            write!(f, "/")?;
+        }
+        write!(f, "{}", self.path)
+    }
+}
+
+// Holds one SFTP session open for as long as the repository is in use
+pub struct SftpRepository {
+    sftp: Sftp,
+    location: String,
+    root: String,
+}
+
+impl std::fmt::Debug for SftpRepository {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("SftpRepository")
+            .field("location", &self.location)
+            .finish()
+    }
+}
+
+fn is_not_found(error: &ssh2::Error) -> bool {
+    error.code() == ErrorCode::SFTP(NO_SUCH_FILE)
+}
+
+// Relative paths are kept relative to the login directory
+fn root_path(path: &str) -> String {
+    match path.trim_end_matches('/') {
+        "" if !path.starts_with('/') => ".".into(),
+        root => root.into(),
+    }
+}
+
+fn remote_path(root: &str, path: &RelativePath) -> PathBuf {
+    let path = path.normalize();
+    if path.as_str().is_empty() {
+        PathBuf::from(if root.is_empty() { "/" } else { root })
+    } else {
+        PathBuf::from(format!("{root}/{path}"))
+    }
+}
+
+impl SftpRepository {
+    fn path(&self, path: &RelativePath) -> PathBuf {
+        remote_path(&self.root, path)
+    }
+
+    fn stat(&self, path: &RelativePath) -> Result<Option<ssh2::FileStat>> {
+        match self.sftp.stat(&self.path(path)) {
+            Ok(stat) => Ok(Some(stat)),
+            Err(error) if is_not_found(&error) => Ok(None),
+            Err(error) => {
+                Err(Error::new(error)
+                    .context(format!("failed to check {path} on {}", self.location)))
+            }
+        }
+    }
+
+    fn create_dirs(&self, path: &RelativePath) -> Result<()> {
+        let mut dir = RelativePathBuf::new();
+        for component in path.normalize().components() {
+            dir.push(component.as_str());
+            match self.stat(&dir)? {
+                Some(stat) if stat.is_dir() => continue,
+                Some(_) => {
+                    return Err(Error::msg(format!("{dir} is not a directory")));
+                }
+                None => self
+                    .sftp
+                    .mkdir(&self.path(&dir), 0o755)
+                    .with_context(|| format!("failed to create directory {dir}"))?,
+            }
+        }
+        Ok(())
+    }
+
+    fn remove_path(&self, path: &Path) -> Result<()> {
+        let stat = match self.sftp.lstat(path) {
+            Ok(stat) => stat,
+            Err(error) if is_not_found(&error) => return Ok(()),
+            Err(error) => return Err(error.into()),
+        };
+        if stat.is_dir() {
+            for (child, _) in self.sftp.readdir(path)? {
+                self.remove_path(&child)?;
+            }
+            self.sftp.rmdir(path)?;
+        } else {
+            self.sftp.unlink(path)?;
+        }
+        Ok(())
+    }
+}
+
+impl Repository for SftpRepository {
+    fn is_file(&self, path: &RelativePath) -> Result<bool> {
+        Ok(self.stat(path)?.is_some_and(|stat| stat.is_file()))
+    }
+
+    fn is_dir(&self, path: &RelativePath) -> Result<bool> {
+        Ok(self.stat(path)?.is_some_and(|stat| stat.is_dir()))
+    }
+
+    fn read_dir(
+        &self,
+        path: &RelativePath,
+    ) -> Result<impl Iterator<Item = Result<RelativePathBuf>> + use<>> {
+        let entries = self
+            .sftp
+            .readdir(self.path(path))
+            .with_context(|| format!("failed to enumerate {path}"))?;
+        let children: Vec<Result<RelativePathBuf>> = entries
+            .into_iter()
+            .map(|(child, _)| {
+                child
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .map(RelativePathBuf::from)
+                    .ok_or(Error::msg(format!(
+                        "failed to enumerate {} in {path}",
+                        child.display()
+                    )))
+            })
+            .collect();
+        Ok(children.into_iter())
+    }
+
+    fn read_file(&self, path: &RelativePath) -> Result<impl std::io::Read + use<>> {
+        self.sftp
+            .open(self.path(path))
+            .with_context(|| format!("failed to read {path}"))
+    }
+
+    fn write_file(&self, path: &RelativePath, content: &mut impl std::io::Read) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            self.create_dirs(parent)?;
+        }
+        // Upload next to the file and rename it into place so readers never see a partial file
+        let temp = self.path(&RelativePathBuf::from(format!(
+            "{}{TEMP_SUFFIX}",
+            path.normalize()
+        )));
+        let mut file = self
+            .sftp
+            .create(&temp)
+            .with_context(|| format!("failed to upload {path}"))?;
+        std::io::copy(content, &mut file).with_context(|| format!("failed to upload {path}"))?;
+        file.close()
+            .with_context(|| format!("failed to upload {path}"))?;
+        let target = self.path(path);
+        let flags = Some(RenameFlags::ATOMIC | RenameFlags::OVERWRITE | RenameFlags::NATIVE);
+        let error = match self.sftp.rename(&temp, &target, flags) {
+            Ok(()) => return Ok(()),
+            Err(error) => error,
+        };
+        // SFTP version 3 servers such as OpenSSH refuse to rename over an existing file, and
+        // report it as a generic failure. ssh2 does not expose posix-rename@openssh.com, so the
+        // old file is moved aside first and put back if the new one can't take its place.
+        if self.stat(path)?.is_none() {
+            let _ = self.sftp.unlink(&temp);
+            return Err(Error::new(error).context(format!("failed to upload {path}")));
+        }
+        let backup = self.path(&RelativePathBuf::from(format!(
+            "{}{BACKUP_SUFFIX}",
+            path.normalize()
+        )));
+        // Left behind by an upload that stopped after moving the old file aside
+        self.remove_path(&backup)
+            .with_context(|| format!("failed to replace {path}"))?;
+        self.sftp
+            .rename(&target, &backup, flags)
+            .with_context(|| format!("failed to replace {path}"))?;
+        if let Err(error) = self.sftp.rename(&temp, &target, flags) {
+            let _ = self.sftp.rename(&backup, &target, flags);
+            let _ = self.sftp.unlink(&temp);
+            return Err(Error::new(error).context(format!("failed to replace {path}")));
+        }
+        let _ = self.sftp.unlink(&backup);
+        Ok(())
+    }
+
+    fn create_file(&self, path: &RelativePath, content: &mut impl std::io::Read) -> Result<bool> {
+        if let Some(parent) = path.parent() {
+            self.create_dirs(parent)?;
+        }
+        let mut file = match self.sftp.open_mode(
+            self.path(path),
+            OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::EXCLUSIVE,
+            0o644,
+            OpenType::File,
+        ) {
+            Ok(file) => file,
+            // SFTP version 3 servers report an existing file as a generic failure
+            Err(_) if self.stat(path)?.is_some() => return Ok(false),
+            Err(error) => {
+                return Err(Error::new(error).context(format!("failed to create {path}")));
+            }
+        };
+        std::io::copy(content, &mut file).with_context(|| format!("failed to write {path}"))?;
+        file.close()
+            .with_context(|| format!("failed to write {path}"))?;
+        Ok(true)
+    }
+
+    fn remove(&self, path: &RelativePath) -> Result<()> {
+        self.remove_path(&self.path(path))
+            .with_context(|| format!("failed to remove {path}"))
+    }
+}
+
+fn verify_host_key(session: &Session, config: &SftpRepositoryConfig) -> Result<()> {
+    let known_hosts_file = match &config.known_hosts {
+        Some(path) => path.clone(),
+        None => home_dir()?.join(".ssh").join("known_hosts"),
+    };
+    let mut known_hosts = session.known_hosts()?;
+    known_hosts
+        .read_file(&known_hosts_file, KnownHostFileKind::OpenSSH)
+        .with_context(|| format!("failed to read {}", known_hosts_file.display()))?;
+    let (key, _) = session
+        .host_key()
+        .ok_or(Error::msg(format!("{} sent no host key", config.host)))?;
+    match known_hosts.check_port(&config.host, config.port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::NotFound => Err(Error::msg(format!(
+            "{} is not in {}, connect to it with ssh once to trust it",
+            config.host,
+            known_hosts_file.display()
+        ))),
+        CheckResult::Mismatch => Err(Error::msg(format!(
+            "the host key of {} does not match the one in {}",
+            config.host,
+            known_hosts_file.display()
+        ))),
+        CheckResult::Failure => Err(Error::msg(format!(
+            "failed to check the host key of {}",
+            config.host
+        ))),
+    }
+}
+
+fn authenticate(session: &Session, config: &SftpRepositoryConfig) -> Result<()> {
+    if let Some(key_file) = &config.key_file {
+        return session
+            .userauth_pubkey_file(&config.user, None, key_file, None)
+            .with_context(|| {
+                format!(
+                    "failed to log in to {} as {} with {}",
+                    config.host,
+                    config.user,
+                    key_file.display()
+                )
+            });
+    }
+    let mut agent = session.agent()?;
+    agent
+        .connect()
+        .with_context(|| "failed to connect to the SSH agent")?;
+    agent.list_identities()?;
+    for identity in agent.identities()? {
+        if agent.userauth(&config.user, &identity).is_ok() {
+            return Ok(());
+        }
+    }
+    Err(Error::msg(format!(
+        "none of the SSH agent keys can log in to {} as {}",
+        config.host, config.user
+    )))
+}
+
+pub fn open_repository(config: &SftpRepositoryConfig) -> Result<SftpRepository> {
+    let stream = TcpStream::connect((config.host.as_str(), config.port))
+        .with_context(|| format!("failed to connect to {}:{}", config.host, config.port))?;
+    let mut session = Session::new()?;
+    session.set_tcp_stream(stream);
+    session
+        .handshake()
+        .with_context(|| format!("failed to start an SSH session with {}", config.host))?;
+    verify_host_key(&session, config)?;
+    authenticate(&session, config)?;
+    let sftp = session
+        .sftp()
+        .with_context(|| format!("failed to start SFTP on {}", config.host))?;
+    let repository = SftpRepository {
+        sftp,
+        location: config.to_string(),
+        root: root_path(&config.path),
+    };
+    if !repository.is_dir(RelativePath::new(""))? {
+        return Result::Err(Error::msg(format!(
+            "Path {} is not a directory on {}",
+            config.path, config.host
+        )));
+    }
+    Ok(repository)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(port: u16, path: &str) -> SftpRepositoryConfig {
+        SftpRepositoryConfig {
+            host: "saves.example.com".into(),
+            port,
+            user: "player".into(),
+            path: path.into(),
+            key_file: None,
+            known_hosts: None,
+        }
+    }
+
+    #[test]
+    fn location_is_shown_as_url() {
+        assert_eq!(
+            config(DEFAULT_PORT, "/srv/saves").to_string(),
+            "sftp://player@saves.example.com/srv/saves"
+        );
+        assert_eq!(
+            config(2222, "saves").to_string(),
+            "sftp://player@saves.example.com:2222/saves"
+        );
+    }
+
+    #[test]
+    fn paths_are_below_the_root() {
+        for (path, root) in [
+            ("/srv/saves/", "/srv/saves"),
+            ("saves", "saves"),
+            ("/", ""),
+            ("", "."),
+        ] {
+            assert_eq!(root_path(path), root, "{path:?}");
+        }
+        let file = RelativePath::new("Some Game/./blobs/a file.bin");
+        assert_eq!(
+            remote_path("/srv/saves", file),
+            PathBuf::from("/srv/saves/Some Game/blobs/a file.bin")
+        );
+        assert_eq!(
+            remote_path(".", file),
+            PathBuf::from("./Some Game/blobs/a file.bin")
+        );
+        assert_eq!(
+            remote_path("", file),
+            PathBuf::from("/Some Game/blobs/a file.bin")
+        );
+        assert_eq!(
+            remote_path("/srv/saves", RelativePath::new("")),
+            PathBuf::from("/srv/saves")
+        );
+        assert_eq!(remote_path("", RelativePath::new("")), PathBuf::from("/"));
+    }
+}
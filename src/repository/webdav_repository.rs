@@ -0,0 +1,368 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use anyhow::{Context, Error, Result};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode};
+use relative_path::{RelativePath, RelativePathBuf};
+use serde::{Deserialize, Serialize};
+use ureq::{
+    Agent, Body, SendBody,
+    http::{Method, Request, Response, StatusCode},
+    tls::{Certificate, RootCerts, TlsConfig},
+};
+
+use super::Repository;
+use crate::utils::files::TEMP_SUFFIX;
+
+const DAV_NAMESPACE: &str = "DAV:";
+pub const PASSWORD_VARIABLE: &str = "GAMESAVESYNC_WEBDAV_PASSWORD";
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?><propfind xmlns="DAV:"><prop><resourcetype/></prop></propfind>"#;
+const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WebDavRepositoryConfig {
+    pub url: String,
+    pub username: Option<String>,
+    // Never saved, taken from GAMESAVESYNC_WEBDAV_PASSWORD when not set
+    #[serde(default, skip_serializing)]
+    pub password: Option<String>,
+    pub ca_certificate: Option<PathBuf>,
+    #[serde(default)]
+    pub accept_invalid_certificates: bool,
+}
+
+impl std::fmt::Display for WebDavRepositoryConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.url)
+    }
+}
+
+pub struct WebDavRepository {
+    agent: Agent,
+    url: String,
+    authorization: Option<String>,
+    // Collections known to exist, so writes only create each parent once
+    collections: RefCell<HashSet<RelativePathBuf>>,
+}
+
+impl std::fmt::Debug for WebDavRepository {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("WebDavRepository")
+            .field("url", &self.url)
+            .finish()
+    }
+}
+
+struct DavEntry {
+    path: String,
+    is_collection: bool,
+}
+
+fn url_path(url: &str) -> &str {
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    without_scheme
+        .find('/')
+        .map_or("", |start| &without_scheme[start..])
+}
+
+fn decode_path(path: &str) -> String {
+    percent_decode_str(path)
+        .decode_utf8_lossy()
+        .trim_end_matches('/')
+        .to_string()
+}
+
+fn parse_multistatus(xml: &str) -> Result<Vec<DavEntry>> {
+    let document =
+        roxmltree::Document::parse(xml).with_context(|| "failed to parse WebDAV response")?;
+    let is_dav = |node: &roxmltree::Node, name: &str| {
+        node.tag_name().name() == name && node.tag_name().namespace() == Some(DAV_NAMESPACE)
+    };
+    let mut entries = vec![];
+    for response in document
+        .descendants()
+        .filter(|node| is_dav(node, "response"))
+    {
+        let href = response
+            .descendants()
+            .find(|node| is_dav(node, "href"))
+            .and_then(|node| node.text())
+            .ok_or(Error::msg("WebDAV response is missing an href"))?;
+        entries.push(DavEntry {
+            path: decode_path(url_path(href.trim())),
+            is_collection: response
+                .descendants()
+                .any(|node| is_dav(&node, "collection")),
+        });
+    }
+    Ok(entries)
+}
+
+impl WebDavRepository {
+    fn url(&self, path: &RelativePath) -> String {
+        let mut url = self.url.clone();
+        for component in path.normalize().components() {
+            url.push('/');
+            url.extend(utf8_percent_encode(component.as_str(), SEGMENT));
+        }
+        url
+    }
+
+    fn request(
+        &self,
+        method: Method,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: SendBody,
+    ) -> Result<Response<Body>> {
+        let mut request = Request::builder().method(method.clone()).uri(url);
+        if let Some(authorization) = &self.authorization {
+            request = request.header("Authorization", authorization);
+        }
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let request = request
+            .body(body)
+            .with_context(|| format!("failed to build WebDAV {method} request for {url}"))?;
+        let response = self
+            .agent
+            .run(request)
+            .with_context(|| format!("WebDAV {method} request for {url} failed"))?;
+        if response.status() == StatusCode::UNAUTHORIZED
+            || response.status() == StatusCode::FORBIDDEN
+        {
+            return Err(Error::msg(format!(
+                "WebDAV server refused access to {url} ({})",
+                response.status()
+            )));
+        }
+        Ok(response)
+    }
+
+    fn propfind(&self, url: &str, depth: &str) -> Result<Option<Vec<DavEntry>>> {
+        let mut response = self.request(
+            Method::from_bytes(b"PROPFIND")?,
+            url,
+            &[
+                ("Depth", depth),
+                ("Content-Type", "application/xml; charset=utf-8"),
+            ],
+            SendBody::from_reader(&mut PROPFIND_BODY.as_bytes()),
+        )?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            StatusCode::MULTI_STATUS => {
+                let xml = response
+                    .body_mut()
+                    .read_to_string()
+                    .with_context(|| format!("failed to read WebDAV listing of {url}"))?;
+                Ok(Some(parse_multistatus(&xml)?))
+            }
+            status => Err(Error::msg(format!(
+                "WebDAV listing of {url} failed with status {status}"
+            ))),
+        }
+    }
+
+    fn entry(&self, path: &RelativePath) -> Result<Option<DavEntry>> {
+        let url = self.url(path);
+        let own_path = decode_path(url_path(&url));
+        Ok(self
+            .propfind(&url, "0")?
+            .and_then(|entries| entries.into_iter().find(|entry| entry.path == own_path)))
+    }
+
+    fn create_collections(&self, path: &RelativePath) -> Result<()> {
+        let mut collection = RelativePathBuf::new();
+        for component in path.normalize().components() {
+            collection.push(component.as_str());
+            if self.collections.borrow().contains(&collection) {
+                continue;
+            }
+            let url = self.url(&collection);
+            let response =
+                self.request(Method::from_bytes(b"MKCOL")?, &url, &[], SendBody::none())?;
+            // Method Not Allowed means the collection is already there
+            if !response.status().is_success()
+                && response.status() != StatusCode::METHOD_NOT_ALLOWED
+            {
+                return Err(Error::msg(format!(
+                    "failed to create WebDAV collection {url} ({})",
+                    response.status()
+                )));
+            }
+            self.collections.borrow_mut().insert(collection.clone());
+        }
+        Ok(())
+    }
+}
+
+impl Repository for WebDavRepository {
+    fn is_file(&self, path: &RelativePath) -> Result<bool> {
+        Ok(self.entry(path)?.is_some_and(|entry| !entry.is_collection))
+    }
+
+    fn is_dir(&self, path: &RelativePath) -> Result<bool> {
+        Ok(self.entry(path)?.is_some_and(|entry| entry.is_collection))
+    }
+
+    fn read_dir(
+        &self,
+        path: &RelativePath,
+    ) -> Result<impl Iterator<Item = Result<RelativePathBuf>> + use<>> {
+        // Collections are listed with a trailing slash so servers do not redirect
+        let url = format!("{}/", self.url(path));
+        let own_path = decode_path(url_path(&url));
+        let entries = self
+            .propfind(&url, "1")?
+            .ok_or(Error::msg(format!("failed to enumerate {path}")))?;
+        let children: Vec<RelativePathBuf> = entries
+            .into_iter()
+            .filter_map(|entry| {
+                let name = entry.path.strip_prefix(&own_path)?.strip_prefix('/')?;
+                (!name.is_empty() && !name.contains('/')).then(|| RelativePathBuf::from(name))
+            })
+            .collect();
+        Ok(children.into_iter().map(Ok))
+    }
+
+    fn read_file(&self, path: &RelativePath) -> Result<impl std::io::Read + use<>> {
+        let url = self.url(path);
+        let response = self.request(Method::GET, &url, &[], SendBody::none())?;
+        if !response.status().is_success() {
+            return Err(Error::msg(format!(
+                "failed to read {path} ({})",
+                response.status()
+            )));
+        }
+        Ok(response.into_body().into_reader())
+    }
+
+    fn write_file(&self, path: &RelativePath, content: &mut impl std::io::Read) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            self.create_collections(parent)?;
+        }
+        // Upload next to the file and move it into place so readers never see a partial file
+        let temp = RelativePathBuf::from(format!("{}{TEMP_SUFFIX}", path.normalize()));
+        let temp_url = self.url(&temp);
+        let response = self.request(Method::PUT, &temp_url, &[], SendBody::from_reader(content))?;
+        if !response.status().is_success() {
+            return Err(Error::msg(format!(
+                "failed to upload {path} ({})",
+                response.status()
+            )));
+        }
+        let url = self.url(path);
+        let response = self.request(
+            Method::from_bytes(b"MOVE")?,
+            &temp_url,
+            &[("Destination", &url), ("Overwrite", "T")],
+            SendBody::none(),
+        )?;
+        if !response.status().is_success() {
+            return Err(Error::msg(format!(
+                "failed to replace {path} ({})",
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+
+    fn create_file(&self, path: &RelativePath, content: &mut impl std::io::Read) -> Result<bool> {
+        if let Some(parent) = path.parent() {
+            self.create_collections(parent)?;
+        }
+        let url = self.url(path);
+        let response = self.request(
+            Method::PUT,
+            &url,
+            &[("If-None-Match", "*")],
+            SendBody::from_reader(content),
+        )?;
+        match response.status() {
+            StatusCode::PRECONDITION_FAILED => Ok(false),
+            status if status.is_success() => Ok(true),
+            status => Err(Error::msg(format!("failed to create {path} ({status})"))),
+        }
+    }
+
+    fn remove(&self, path: &RelativePath) -> Result<()> {
+        let mut url = self.url(path);
+        if self.is_dir(path)? {
+            url.push('/');
+        }
+        let response = self.request(Method::DELETE, &url, &[], SendBody::none())?;
+        if !response.status().is_success() && response.status() != StatusCode::NOT_FOUND {
+            return Err(Error::msg(format!(
+                "failed to remove {path} ({})",
+                response.status()
+            )));
+        }
+        let normalized = path.normalize();
+        self.collections
+            .borrow_mut()
+            .retain(|collection| !collection.starts_with(&normalized));
+        Ok(())
+    }
+}
+
+fn tls_config(config: &WebDavRepositoryConfig) -> Result<TlsConfig> {
+    let mut tls = TlsConfig::builder().disable_verification(config.accept_invalid_certificates);
+    if let Some(path) = &config.ca_certificate {
+        let pem = std::fs::read(path)
+            .with_context(|| format!("failed to read certificate {}", path.display()))?;
+        let certificate = Certificate::from_pem(&pem)
+            .with_context(|| format!("failed to parse certificate {}", path.display()))?;
+        tls = tls.root_certs(RootCerts::new_with_certs(&[certificate]));
+    }
+    Ok(tls.build())
+}
+
+pub fn open_repository(config: &WebDavRepositoryConfig) -> Result<WebDavRepository> {
+    if !config.url.starts_with("http://") && !config.url.starts_with("https://") {
+        return Result::Err(Error::msg(format!(
+            "URL {} is not an http or https URL",
+            config.url
+        )));
+    }
+    let agent: Agent = Agent::config_builder()
+        .http_status_as_error(false)
+        .allow_non_standard_methods(true)
+        .tls_config(tls_config(config)?)
+        .build()
+        .into();
+    let authorization = match &config.username {
+        Some(username) => {
+            let password = config
+                .password
+                .clone()
+                .or_else(|| std::env::var(PASSWORD_VARIABLE).ok())
+                .ok_or(Error::msg(format!(
+                    "no password for {username} set in {PASSWORD_VARIABLE}"
+                )))?;
+            let credentials = format!("{username}:{password}");
+            Some(format!("Basic {}", BASE64_STANDARD.encode(credentials)))
+        }
+        None => None,
+    };
+    let repository = WebDavRepository {
+        agent,
+        url: config.url.trim_end_matches('/').to_string(),
+        authorization,
+        collections: RefCell::new(HashSet::new()),
+    };
+    if !repository.is_dir(RelativePath::new(""))? {
+        return Result::Err(Error::msg(format!(
+            "URL {} is not a WebDAV collection",
+            config.url
+        )));
+    }
+    Ok(repository)
+}
@@ -0,0 +1,929 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Error, Result};
+use relative_path::RelativePath;
+use time::macros::format_description;
+use uuid::Uuid;
+
+use crate::{
+    games::{
+        ConflictPolicy,
+        blobs::{blob_path, collect_garbage, stored_file_path},
+        definition::{GameDefinition, definition_exists, load_definition},
+        history::{find_branch, prune_snapshots, write_snapshot_manifest},
+        journal::{begin_apply, finish_apply, rollback_apply},
+        manifest::{
+            GameSaveFileMetadata, GameSaveManifest, GameSaveStorage, ManifestChange,
+            read_repository_manifest, read_synced_manifest, write_repository_manifest,
+            write_synced_manifest,
+        },
+    },
+    repository::{
+        Repository,
+        lock::{LockEvent, RepositoryLock, acquire_lock},
+        settings::read_settings,
+    },
+    utils::{
+        clock::now,
+        config::Config,
+        device::device_name,
+        files::write_file_atomic,
+        paths::{data_dir, make_path_safe},
+    },
+};
+
+pub mod callbacks;
+pub mod diff;
+pub mod files;
+pub mod status;
+
+use callbacks::{
+    Conflict, ConflictReason, ConflictResolution, ConflictResolver, SaveSide, SyncEvent,
+    SyncObserver, TransferDirection,
+};
+use files::{
+    ResolvedSaveFiles, get_local_files, get_manifest_files, hash_file, save_file_equal,
+    save_files_equal,
+};
+
+const BACKUPS_DIR: &str = "backups";
+const DEFAULT_LOCK_TIMEOUT: u64 = 30;
+
+#[derive(Default, Debug)]
+pub struct SyncOptions {
+    pub dry_run: bool,
+    // Overrides the conflict policy of the game
+    pub on_conflict: Option<ConflictPolicy>,
+    // Seconds to wait for another device's lock, DEFAULT_LOCK_TIMEOUT when not given
+    pub lock_timeout: Option<u64>,
+    pub break_lock: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SyncAction {
+    UpToDate,
+    Stored,
+    Applied,
+    Merged,
+    Skipped,
+}
+
+#[derive(Debug)]
+pub struct SyncReport {
+    pub game: String,
+    pub action: SyncAction,
+    pub rolled_back: bool,
+    pub branches: Vec<String>,
+    pub pruned: Vec<Uuid>,
+}
+
+impl SyncReport {
+    fn new(game: &str) -> Self {
+        SyncReport {
+            game: game.into(),
+            action: SyncAction::UpToDate,
+            rolled_back: false,
+            branches: vec![],
+            pruned: vec![],
+        }
+    }
+}
+
+pub fn sync_games(
+    repository: &impl Repository,
+    config: &Config,
+    game: Option<&str>,
+    options: &SyncOptions,
+    resolver: &mut impl ConflictResolver,
+    observer: &mut impl SyncObserver,
+) -> Result<Vec<SyncReport>> {
+    if !options.dry_run {
+        repository.pull()?;
+    }
+    let reports = sync_linked_games(repository, config, game, options, resolver, observer);
+    // Push even when a game failed, the games synced before it already recorded the sync locally
+    let pushed = if options.dry_run {
+        Ok(())
+    } else {
+        repository.push()
+    };
+    let reports = reports?;
+    pushed?;
+    Ok(reports)
+}
+
+fn sync_linked_games(
+    repository: &impl Repository,
+    config: &Config,
+    game: Option<&str>,
+    options: &SyncOptions,
+    resolver: &mut impl ConflictResolver,
+    observer: &mut impl SyncObserver,
+) -> Result<Vec<SyncReport>> {
+    let mut reports = vec![];
+    match game {
+        Some(game) if !config.linked_games.contains(game) => {
+            return Err(Error::msg(format!(
+                "game {game} is not linked on this device"
+            )));
+        }
+        Some(game) => reports.push(sync_game(game, repository, options, resolver, observer)?),
+        None => {
+            for game in &config.linked_games {
+                if !definition_exists(repository, game)? {
+                    observer.on_event(&SyncEvent::GameNotDefined { game });
+                    continue;
+                }
+                reports.push(sync_game(game, repository, options, resolver, observer)?);
+            }
+        }
+    }
+    Ok(reports)
+}
+
+#[derive(Debug)]
+enum SyncDirection {
+    ToRepository,
+    FromRepository,
+    // Take the listed files from the repository and store the merged save
+    Merge {
+        from_repository: Vec<PathBuf>,
+        local_won_conflicts: bool,
+    },
+    DoNothing,
+    UpToDate,
+}
+
+impl From<ConflictResolution> for (SyncDirection, bool) {
+    fn from(resolution: ConflictResolution) -> Self {
+        match resolution {
+            ConflictResolution::DoNothing => (SyncDirection::DoNothing, false),
+            ConflictResolution::UseLocal { keep_other } => {
+                (SyncDirection::ToRepository, keep_other)
+            }
+            ConflictResolution::UseRepository { keep_other } => {
+                (SyncDirection::FromRepository, keep_other)
+            }
+        }
+    }
+}
+
+pub type ManifestState = (ResolvedSaveFiles, GameSaveManifest);
+
+pub struct GameSaveState {
+    pub repository: Option<ManifestState>,
+    pub synced: Option<ManifestState>,
+    pub local_files: ResolvedSaveFiles,
+}
+
+pub fn load_save_state(
+    definition: &GameDefinition,
+    repository: &impl Repository,
+) -> Result<GameSaveState> {
+    let game = &definition.name;
+    let repository_state = match read_repository_manifest(repository, game)? {
+        Some(manifest) => Some((get_manifest_files(&manifest, definition)?, manifest)),
+        None => None,
+    };
+    let synced_state = match read_synced_manifest(game)? {
+        Some(manifest) => Some((get_manifest_files(&manifest, definition)?, manifest)),
+        None => None,
+    };
+    let local_files = get_local_files(definition, synced_state.as_ref().map(|state| &state.0))?;
+    Ok(GameSaveState {
+        repository: repository_state,
+        synced: synced_state,
+        local_files,
+    })
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SaveChanges {
+    Unchanged,
+    LocalChanged,
+    RepositoryChanged,
+    BothChanged,
+}
+
+pub fn detect_changes(
+    local_files: &ResolvedSaveFiles,
+    synced_files: &ResolvedSaveFiles,
+    repository_files: &ResolvedSaveFiles,
+) -> SaveChanges {
+    let local_changed = !save_files_equal(local_files, synced_files);
+    let repository_changed = !save_files_equal(synced_files, repository_files);
+    match (local_changed, repository_changed) {
+        (true, true) => SaveChanges::BothChanged,
+        (true, false) => SaveChanges::LocalChanged,
+        (false, true) => SaveChanges::RepositoryChanged,
+        (false, false) => SaveChanges::Unchanged,
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct FileChanges {
+    pub local: Vec<PathBuf>,
+    pub repository: Vec<PathBuf>,
+    // Files changed differently on both sides
+    pub conflicts: Vec<PathBuf>,
+}
+
+pub fn classify_file_changes(
+    local_files: &ResolvedSaveFiles,
+    synced_files: &ResolvedSaveFiles,
+    repository_files: &ResolvedSaveFiles,
+) -> FileChanges {
+    let mut changes = FileChanges::default();
+    let paths: HashSet<&PathBuf> = local_files
+        .keys()
+        .chain(synced_files.keys())
+        .chain(repository_files.keys())
+        .collect();
+    for path in paths {
+        let local = local_files.get(path);
+        let synced = synced_files.get(path);
+        let repository = repository_files.get(path);
+        match (
+            !save_file_equal(local, synced),
+            !save_file_equal(synced, repository),
+        ) {
+            (true, true) if save_file_equal(local, repository) => {}
+            (true, true) => changes.conflicts.push(path.clone()),
+            (true, false) => changes.local.push(path.clone()),
+            (false, true) => changes.repository.push(path.clone()),
+            (false, false) => {}
+        }
+    }
+    changes.conflicts.sort();
+    changes
+}
+
+pub fn sync_game(
+    game: &str,
+    repository: &impl Repository,
+    options: &SyncOptions,
+    resolver: &mut impl ConflictResolver,
+    observer: &mut impl SyncObserver,
+) -> Result<SyncReport> {
+    observer.on_event(&SyncEvent::Checking { game });
+    match lock_game(game, repository, options, observer)? {
+        // Going through the lock keeps it fresh for as long as the sync takes
+        Some(lock) => sync_locked_game(game, &lock, options, resolver, observer),
+        None => sync_locked_game(game, repository, options, resolver, observer),
+    }
+}
+
+// Called with the game locked, or with the repository itself in a dry run
+fn sync_locked_game(
+    game: &str,
+    repository: &impl Repository,
+    options: &SyncOptions,
+    resolver: &mut impl ConflictResolver,
+    observer: &mut impl SyncObserver,
+) -> Result<SyncReport> {
+    let mut report = SyncReport::new(game);
+    if !options.dry_run && rollback_apply(game)? {
+        observer.on_event(&SyncEvent::RolledBack { game });
+        report.rolled_back = true;
+    }
+    let definition = load_definition(repository, game)?;
+    let GameSaveState {
+        repository: repository_state,
+        synced: synced_state,
+        local_files,
+    } = load_save_state(&definition, repository)?;
+    let conflict_policy = options
+        .on_conflict
+        .or(definition.conflict_policy)
+        .unwrap_or(ConflictPolicy::Ask);
+    let (sync_direction, keep_both) = match &repository_state {
+        Some((repository_files, repository_manifest)) => match &synced_state {
+            Some((synced_files, synced_manifest)) => {
+                match detect_changes(&local_files, synced_files, repository_files) {
+                    SaveChanges::BothChanged => merge_changes(
+                        game,
+                        synced_manifest,
+                        conflict_policy,
+                        &local_files,
+                        synced_files,
+                        repository_files,
+                        repository_manifest,
+                        resolver,
+                        observer,
+                    )?,
+                    SaveChanges::LocalChanged => (SyncDirection::ToRepository, false),
+                    SaveChanges::RepositoryChanged => (SyncDirection::FromRepository, false),
+                    SaveChanges::Unchanged => (SyncDirection::UpToDate, false),
+                }
+            }
+            // No local manifest
+            None => resolve_conflict(
+                &Conflict {
+                    game,
+                    reason: ConflictReason::NeverSynced,
+                    local_files: &local_files,
+                    synced_files: None,
+                    repository_files,
+                    repository_synced: repository_manifest.timestamp,
+                },
+                conflict_policy,
+                resolver,
+                observer,
+            )?
+            .into(),
+        },
+        // No repository manifest
+        None => (SyncDirection::ToRepository, false),
+    };
+    match sync_direction {
+        SyncDirection::ToRepository => {
+            if keep_both && let Some((_, manifest)) = &repository_state {
+                let branch = branch_repository_save(manifest, repository, options, observer)?;
+                report.branches.push(branch);
+            }
+            observer.on_event(&SyncEvent::Storing { game });
+            report.pruned = sync_game_to_repository(
+                &definition,
+                &local_files,
+                ManifestChange::Stored,
+                repository,
+                options,
+                observer,
+            )?;
+            report.action = SyncAction::Stored;
+        }
+        SyncDirection::FromRepository => {
+            if keep_both
+                && let Some(branch) =
+                    branch_local_save(&definition, &local_files, repository, options, observer)?
+            {
+                report.branches.push(branch);
+            }
+            observer.on_event(&SyncEvent::Applying { game });
+            match repository_state {
+                Some((files, manifest)) => sync_game_from_repository(
+                    &manifest,
+                    &files,
+                    &local_files,
+                    repository,
+                    options,
+                    observer,
+                )?,
+                None => {
+                    unreachable!("impossible to sync from repository with no repository manifest")
+                }
+            }
+            report.action = SyncAction::Applied;
+        }
+        SyncDirection::Merge {
+            from_repository,
+            local_won_conflicts,
+        } => {
+            let (repository_files, repository_manifest) = match &repository_state {
+                Some(state) => state,
+                None => unreachable!("impossible to merge with no repository manifest"),
+            };
+            let branch = if keep_both && local_won_conflicts {
+                Some(branch_repository_save(
+                    repository_manifest,
+                    repository,
+                    options,
+                    observer,
+                )?)
+            } else if keep_both {
+                branch_local_save(&definition, &local_files, repository, options, observer)?
+            } else {
+                None
+            };
+            report.branches.extend(branch);
+            observer.on_event(&SyncEvent::Merging { game });
+            report.pruned = sync_game_merge(
+                &definition,
+                &local_files,
+                repository_manifest,
+                repository_files,
+                &from_repository,
+                repository,
+                options,
+                observer,
+            )?;
+            report.action = SyncAction::Merged;
+        }
+        SyncDirection::UpToDate => {}
+        SyncDirection::DoNothing => report.action = SyncAction::Skipped,
+    }
+    observer.on_event(&SyncEvent::Finished { game });
+    Ok(report)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn merge_changes(
+    game: &str,
+    synced_manifest: &GameSaveManifest,
+    policy: ConflictPolicy,
+    local_files: &ResolvedSaveFiles,
+    synced_files: &ResolvedSaveFiles,
+    repository_files: &ResolvedSaveFiles,
+    repository_manifest: &GameSaveManifest,
+    resolver: &mut impl ConflictResolver,
+    observer: &mut impl SyncObserver,
+) -> Result<(SyncDirection, bool)> {
+    let FileChanges {
+        local: mut local_changes,
+        repository: mut from_repository,
+        conflicts,
+    } = classify_file_changes(local_files, synced_files, repository_files);
+    let mut keep_both = false;
+    let mut local_won_conflicts = false;
+    if !conflicts.is_empty() {
+        let subset = |files: &ResolvedSaveFiles| -> ResolvedSaveFiles {
+            conflicts
+                .iter()
+                .filter_map(|path| files.get_key_value(path))
+                .map(|(path, file)| (path.clone(), file.clone()))
+                .collect()
+        };
+        let names: Vec<String> = conflicts
+            .iter()
+            .filter_map(|path| local_files.get(path).or(repository_files.get(path)))
+            .map(|(_, file, _)| file.to_string())
+            .collect();
+        let resolution = resolve_conflict(
+            &Conflict {
+                game,
+                reason: ConflictReason::BothChanged {
+                    last_synced: synced_manifest.timestamp,
+                    files: names,
+                },
+                local_files: &subset(local_files),
+                synced_files: Some(&subset(synced_files)),
+                repository_files: &subset(repository_files),
+                repository_synced: repository_manifest.timestamp,
+            },
+            policy,
+            resolver,
+            observer,
+        )?;
+        match resolution {
+            ConflictResolution::UseLocal { keep_other } => {
+                keep_both = keep_other;
+                local_won_conflicts = true;
+                local_changes.extend(conflicts);
+            }
+            ConflictResolution::UseRepository { keep_other } => {
+                keep_both = keep_other;
+                from_repository.extend(conflicts);
+            }
+            ConflictResolution::DoNothing => return Ok((SyncDirection::DoNothing, false)),
+        }
+    }
+    let sync_direction = if local_changes.is_empty() {
+        SyncDirection::FromRepository
+    } else if from_repository.is_empty() {
+        SyncDirection::ToRepository
+    } else {
+        SyncDirection::Merge {
+            from_repository,
+            local_won_conflicts,
+        }
+    };
+    Ok((sync_direction, keep_both))
+}
+
+pub fn lock_game<'a, R: Repository>(
+    game: &str,
+    repository: &'a R,
+    options: &SyncOptions,
+    observer: &mut impl SyncObserver,
+) -> Result<Option<RepositoryLock<'a, R>>> {
+    if options.dry_run {
+        return Ok(None);
+    }
+    let timeout = Duration::from_secs(options.lock_timeout.unwrap_or(DEFAULT_LOCK_TIMEOUT));
+    let lock = acquire_lock(
+        repository,
+        RelativePath::new(game),
+        timeout,
+        options.break_lock,
+        &mut |event| match event {
+            LockEvent::Waiting(holder) => {
+                observer.on_event(&SyncEvent::LockWaiting { game, holder })
+            }
+            LockEvent::Breaking(holder) => {
+                observer.on_event(&SyncEvent::LockBroken { game, holder })
+            }
+        },
+    )?;
+    Ok(Some(lock))
+}
+
+fn resolve_conflict(
+    conflict: &Conflict,
+    policy: ConflictPolicy,
+    resolver: &mut impl ConflictResolver,
+    observer: &mut impl SyncObserver,
+) -> Result<ConflictResolution> {
+    // Policies run unattended, so they don't leave branches behind for someone to clean up
+    let resolution = match policy {
+        ConflictPolicy::Ask => return resolver.resolve(conflict),
+        ConflictPolicy::Skip => ConflictResolution::DoNothing,
+        ConflictPolicy::PreferLocal => ConflictResolution::UseLocal { keep_other: false },
+        ConflictPolicy::PreferRepository => ConflictResolution::UseRepository { keep_other: false },
+        ConflictPolicy::PreferNewest
+            if conflict.local_modified() > conflict.repository_modified() =>
+        {
+            ConflictResolution::UseLocal { keep_other: false }
+        }
+        ConflictPolicy::PreferNewest => ConflictResolution::UseRepository { keep_other: false },
+        ConflictPolicy::Fail => return Err(Error::msg(conflict.to_string())),
+    };
+    observer.on_event(&SyncEvent::ConflictResolved {
+        conflict,
+        policy,
+        resolution,
+    });
+    Ok(resolution)
+}
+
+// Names only go down to the second, so later branches from the same second are numbered
+fn conflict_branch_name(repository: &impl Repository, game: &str) -> Result<String> {
+    let name = format!("conflict-{}-{}", device_name(), timestamp_label()?);
+    let mut branch = name.clone();
+    let mut number = 1;
+    while find_branch(repository, game, &branch)?.is_some() {
+        number += 1;
+        branch = format!("{name}-{number}");
+    }
+    Ok(branch)
+}
+
+fn branch_repository_save(
+    manifest: &GameSaveManifest,
+    repository: &impl Repository,
+    options: &SyncOptions,
+    observer: &mut impl SyncObserver,
+) -> Result<String> {
+    let branch = conflict_branch_name(repository, &manifest.definition.name)?;
+    observer.on_event(&SyncEvent::Branching {
+        game: &manifest.definition.name,
+        side: SaveSide::Repository,
+        branch: &branch,
+    });
+    if !options.dry_run {
+        write_snapshot_manifest(
+            &GameSaveManifest {
+                branch: Some(branch.clone()),
+                ..manifest.clone()
+            },
+            repository,
+        )?;
+    }
+    Ok(branch)
+}
+
+fn branch_local_save(
+    definition: &GameDefinition,
+    local_files: &ResolvedSaveFiles,
+    repository: &impl Repository,
+    options: &SyncOptions,
+    observer: &mut impl SyncObserver,
+) -> Result<Option<String>> {
+    if local_files.is_empty() {
+        return Ok(None);
+    }
+    let branch = conflict_branch_name(repository, &definition.name)?;
+    observer.on_event(&SyncEvent::Branching {
+        game: &definition.name,
+        side: SaveSide::Local,
+        branch: &branch,
+    });
+    let manifest = store_local_save(definition, local_files, repository, options, observer)?;
+    if !options.dry_run {
+        write_snapshot_manifest(
+            &GameSaveManifest {
+                branch: Some(branch.clone()),
+                ..manifest
+            },
+            repository,
+        )?;
+    }
+    Ok(Some(branch))
+}
+
+fn store_local_save(
+    definition: &GameDefinition,
+    local_files: &ResolvedSaveFiles,
+    repository: &impl Repository,
+    options: &SyncOptions,
+    observer: &mut impl SyncObserver,
+) -> Result<GameSaveManifest> {
+    let game = &definition.name;
+    let mut files = HashMap::new();
+    let mut to_store = vec![];
+    for (real_path, (path, file, metadata)) in local_files {
+        let hash = metadata.hash.as_ref().ok_or(Error::msg(format!(
+            "{} has no content hash",
+            real_path.display()
+        )))?;
+        let repository_path = blob_path(game, hash);
+        // Unchanged files are already stored by an earlier snapshot
+        if !repository.is_file(&repository_path)? {
+            to_store.push((real_path, repository_path, metadata.size));
+        }
+        files
+            .entry(path.clone())
+            .or_insert_with(HashMap::new)
+            .insert(file.clone(), metadata.clone());
+    }
+    observer.on_event(&SyncEvent::Transferring {
+        game,
+        direction: TransferDirection::ToRepository,
+        files: to_store.len(),
+        bytes: to_store.iter().map(|(_, _, size)| size).sum(),
+    });
+    for (real_path, repository_path, size) in to_store {
+        if !options.dry_run {
+            let mut local_file = std::fs::File::open(real_path)?;
+            repository
+                .write_file(&repository_path, &mut local_file)
+                .with_context(|| format!("failed to copy {} to repository", real_path.display()))?;
+        }
+        observer.on_event(&SyncEvent::FileCopied {
+            game,
+            path: real_path,
+            direction: TransferDirection::ToRepository,
+            bytes: size,
+        });
+    }
+    let old_manifest = read_repository_manifest(repository, game)?;
+    Ok(GameSaveManifest {
+        id: Uuid::new_v4(),
+        parent: old_manifest.map(|old_manifest| old_manifest.id),
+        device: Some(device_name()),
+        branch: None,
+        storage: GameSaveStorage::Blobs,
+        definition: definition.clone(),
+        timestamp: now(),
+        files,
+    })
+}
+
+fn sync_game_to_repository(
+    definition: &GameDefinition,
+    local_files: &ResolvedSaveFiles,
+    change: ManifestChange,
+    repository: &impl Repository,
+    options: &SyncOptions,
+    observer: &mut impl SyncObserver,
+) -> Result<Vec<Uuid>> {
+    let manifest = store_local_save(definition, local_files, repository, options, observer)?;
+    if options.dry_run {
+        return Ok(vec![]);
+    }
+    write_repository_manifest(&manifest, change, repository)?;
+    write_synced_manifest(&manifest)?;
+    let settings = read_settings(repository)?;
+    let pruned = prune_snapshots(repository, &definition.name, &settings.retention)?;
+    for id in &pruned {
+        observer.on_event(&SyncEvent::SnapshotPruned {
+            game: &definition.name,
+            id: *id,
+        });
+    }
+    collect_garbage(repository, &definition.name)?;
+    if !pruned.is_empty() {
+        repository.commit(&format!(
+            "Prune {} snapshots from {}",
+            definition.name,
+            device_name()
+        ))?;
+    }
+    Ok(pruned)
+}
+
+fn apply_repository_file(
+    manifest: &GameSaveManifest,
+    real_path: &Path,
+    path: &str,
+    file: &RelativePath,
+    metadata: &GameSaveFileMetadata,
+    repository: &impl Repository,
+) -> Result<()> {
+    let repository_path = stored_file_path(manifest, path, file, metadata)?;
+    let mut repository_file = repository.read_file(&repository_path)?;
+    write_file_atomic(
+        real_path,
+        &mut repository_file,
+        Some(metadata.modified.into()),
+    )
+    .with_context(|| format!("failed to copy {} from repository", real_path.display()))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn sync_game_merge(
+    definition: &GameDefinition,
+    local_files: &ResolvedSaveFiles,
+    repository_manifest: &GameSaveManifest,
+    repository_files: &ResolvedSaveFiles,
+    from_repository: &[PathBuf],
+    repository: &impl Repository,
+    options: &SyncOptions,
+    observer: &mut impl SyncObserver,
+) -> Result<Vec<Uuid>> {
+    if !options.dry_run {
+        begin_apply(
+            &definition.name,
+            from_repository.iter().map(PathBuf::as_path),
+        )?;
+    }
+    let game = &definition.name;
+    let to_apply: Vec<&GameSaveFileMetadata> = from_repository
+        .iter()
+        .filter_map(|path| repository_files.get(path))
+        .map(|(_, _, metadata)| metadata)
+        .collect();
+    observer.on_event(&SyncEvent::Transferring {
+        game,
+        direction: TransferDirection::FromRepository,
+        files: to_apply.len(),
+        bytes: to_apply.iter().map(|metadata| metadata.size).sum(),
+    });
+    let mut merged_files = local_files.clone();
+    for real_path in from_repository {
+        match repository_files.get(real_path) {
+            Some((path, file, metadata)) => {
+                if !options.dry_run {
+                    apply_repository_file(
+                        repository_manifest,
+                        real_path,
+                        path,
+                        file,
+                        metadata,
+                        repository,
+                    )?;
+                }
+                observer.on_event(&SyncEvent::FileCopied {
+                    game,
+                    path: real_path,
+                    direction: TransferDirection::FromRepository,
+                    bytes: metadata.size,
+                });
+                let mut metadata = metadata.clone();
+                // Saves stored before content hashing need one to be stored again
+                if metadata.hash.is_none() && !options.dry_run {
+                    metadata.hash = Some(hash_file(real_path)?);
+                }
+                merged_files.insert(real_path.clone(), (path.clone(), file.clone(), metadata));
+            }
+            None => {
+                observer.on_event(&SyncEvent::FileRemoved {
+                    game,
+                    path: real_path,
+                });
+                if !options.dry_run && real_path.is_file() {
+                    std::fs::remove_file(real_path)?;
+                }
+                merged_files.remove(real_path);
+            }
+        }
+    }
+    // The local files are complete, so a failure storing them must not roll them back
+    if !options.dry_run {
+        finish_apply(game)?;
+    }
+    sync_game_to_repository(
+        definition,
+        &merged_files,
+        ManifestChange::Merged,
+        repository,
+        options,
+        observer,
+    )
+}
+
+pub fn sync_game_from_repository(
+    manifest: &GameSaveManifest,
+    repository_files: &ResolvedSaveFiles,
+    local_files: &ResolvedSaveFiles,
+    repository: &impl Repository,
+    options: &SyncOptions,
+    observer: &mut impl SyncObserver,
+) -> Result<()> {
+    let game = &manifest.definition.name;
+    if !options.dry_run {
+        begin_apply(
+            game,
+            local_files
+                .keys()
+                .chain(repository_files.keys())
+                .map(PathBuf::as_path),
+        )?;
+    }
+    // Only files found by the local scan are removed, so excluded files are left alone
+    for path in local_files.keys() {
+        if repository_files.contains_key(path) {
+            continue;
+        }
+        observer.on_event(&SyncEvent::FileRemoved { game, path });
+        if !options.dry_run && path.is_file() {
+            std::fs::remove_file(path)?;
+        }
+    }
+    observer.on_event(&SyncEvent::Transferring {
+        game,
+        direction: TransferDirection::FromRepository,
+        files: repository_files.len(),
+        bytes: repository_files
+            .values()
+            .map(|(_, _, metadata)| metadata.size)
+            .sum(),
+    });
+    for (real_path, (path, file, metadata)) in repository_files {
+        if !options.dry_run {
+            apply_repository_file(manifest, real_path, path, file, metadata, repository)?;
+        }
+        observer.on_event(&SyncEvent::FileCopied {
+            game,
+            path: real_path,
+            direction: TransferDirection::FromRepository,
+            bytes: metadata.size,
+        });
+    }
+    if !options.dry_run {
+        // Once the synced manifest matches the files they must not be rolled back
+        finish_apply(game)?;
+        write_synced_manifest(&GameSaveManifest {
+            timestamp: now(),
+            ..manifest.clone()
+        })?;
+    }
+    Ok(())
+}
+
+// Applies a stored snapshot on this device, called with the game locked
+pub fn restore_snapshot(
+    snapshot: &GameSaveManifest,
+    local_files: &ResolvedSaveFiles,
+    make_current: bool,
+    repository: &impl Repository,
+    options: &SyncOptions,
+    observer: &mut impl SyncObserver,
+) -> Result<()> {
+    let game = &snapshot.definition.name;
+    let head = read_repository_manifest(repository, game)?;
+    // Restored like a promoted branch, as a new save rather than the old one
+    let restored = GameSaveManifest {
+        branch: None,
+        timestamp: now(),
+        ..snapshot.clone()
+    };
+    let snapshot_files = get_manifest_files(&restored, &restored.definition)?;
+    sync_game_from_repository(
+        &restored,
+        &snapshot_files,
+        local_files,
+        repository,
+        options,
+        observer,
+    )?;
+    if options.dry_run {
+        return Ok(());
+    }
+    if make_current {
+        write_repository_manifest(&restored, ManifestChange::Restored, repository)?;
+    } else if let Some(head) = head {
+        // Leave the restored save as a local change so the next sync stores it
+        write_synced_manifest(&GameSaveManifest {
+            timestamp: now(),
+            ..head
+        })?;
+    }
+    Ok(())
+}
+
+fn timestamp_label() -> Result<String> {
+    let format = format_description!("[year]-[month]-[day]_[hour]-[minute]-[second]");
+    Ok(now().format(format)?)
+}
+
+pub fn backup_local_files(game: &str, local_files: &ResolvedSaveFiles) -> Result<Option<PathBuf>> {
+    if local_files.is_empty() {
+        return Ok(None);
+    }
+    let backup_dir = data_dir()?
+        .join(game)
+        .join(BACKUPS_DIR)
+        .join(timestamp_label()?);
+    for (real_path, (path, file, _)) in local_files {
+        let backup_path = file.to_path(backup_dir.join(make_path_safe(path)));
+        match backup_path.parent() {
+            Some(parent) => std::fs::create_dir_all(parent)?,
+            None => unreachable!("backup file paths must have a parent"),
+        }
+        std::fs::copy(real_path, &backup_path)
+            .with_context(|| format!("failed to back up {}", real_path.display()))?;
+    }
+    Ok(Some(backup_dir))
+}
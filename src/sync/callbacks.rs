@@ -0,0 +1,161 @@
+use std::path::Path;
+
+use anyhow::Result;
+use time::{OffsetDateTime, UtcDateTime};
+use uuid::Uuid;
+
+use crate::{games::ConflictPolicy, repository::lock::LockHolder};
+
+use super::files::ResolvedSaveFiles;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ConflictReason {
+    NeverSynced,
+    BothChanged {
+        last_synced: OffsetDateTime,
+        files: Vec<String>,
+    },
+}
+
+#[derive(Debug)]
+pub struct Conflict<'a> {
+    pub game: &'a str,
+    pub reason: ConflictReason,
+    pub local_files: &'a ResolvedSaveFiles,
+    pub synced_files: Option<&'a ResolvedSaveFiles>,
+    pub repository_files: &'a ResolvedSaveFiles,
+    pub repository_synced: OffsetDateTime,
+}
+
+impl Conflict<'_> {
+    pub fn local_modified(&self) -> Option<UtcDateTime> {
+        self.local_files.values().map(|file| file.2.modified).max()
+    }
+
+    pub fn repository_modified(&self) -> Option<UtcDateTime> {
+        self.repository_files
+            .values()
+            .map(|file| file.2.modified)
+            .max()
+    }
+}
+
+impl std::fmt::Display for Conflict<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.reason {
+            ConflictReason::NeverSynced => {
+                write!(f, "{} has not been synced to this device", self.game)
+            }
+            ConflictReason::BothChanged { last_synced, files } => write!(
+                f,
+                "{} has changed here and in the repository (last synced at {}), both changed {}",
+                self.game,
+                last_synced,
+                files.join(", ")
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ConflictResolution {
+    DoNothing,
+    UseLocal { keep_other: bool },
+    UseRepository { keep_other: bool },
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SaveSide {
+    Local,
+    Repository,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TransferDirection {
+    ToRepository,
+    FromRepository,
+}
+
+#[derive(Debug)]
+pub enum SyncEvent<'a> {
+    Checking {
+        game: &'a str,
+    },
+    GameNotDefined {
+        game: &'a str,
+    },
+    LockWaiting {
+        game: &'a str,
+        holder: &'a LockHolder,
+    },
+    LockBroken {
+        game: &'a str,
+        holder: &'a LockHolder,
+    },
+    RolledBack {
+        game: &'a str,
+    },
+    ConflictResolved {
+        conflict: &'a Conflict<'a>,
+        policy: ConflictPolicy,
+        resolution: ConflictResolution,
+    },
+    Branching {
+        game: &'a str,
+        side: SaveSide,
+        branch: &'a str,
+    },
+    Storing {
+        game: &'a str,
+    },
+    Applying {
+        game: &'a str,
+    },
+    Merging {
+        game: &'a str,
+    },
+    // Sent before a batch of file copies with the totals they add up to
+    Transferring {
+        game: &'a str,
+        direction: TransferDirection,
+        files: usize,
+        bytes: u64,
+    },
+    FileCopied {
+        game: &'a str,
+        path: &'a Path,
+        direction: TransferDirection,
+        bytes: u64,
+    },
+    FileRemoved {
+        game: &'a str,
+        path: &'a Path,
+    },
+    SnapshotPruned {
+        game: &'a str,
+        id: Uuid,
+    },
+    Finished {
+        game: &'a str,
+    },
+}
+
+pub trait ConflictResolver {
+    fn resolve(&mut self, conflict: &Conflict) -> Result<ConflictResolution>;
+}
+
+impl<F: FnMut(&Conflict) -> Result<ConflictResolution>> ConflictResolver for F {
+    fn resolve(&mut self, conflict: &Conflict) -> Result<ConflictResolution> {
+        self(conflict)
+    }
+}
+
+pub trait SyncObserver {
+    fn on_event(&mut self, event: &SyncEvent);
+}
+
+impl<F: FnMut(&SyncEvent)> SyncObserver for F {
+    fn on_event(&mut self, event: &SyncEvent) {
+        self(event)
+    }
+}
@@ -0,0 +1,88 @@
+use std::path::PathBuf;
+
+use crate::games::manifest::GameSaveFileMetadata;
+
+use super::files::{ResolvedSaveFile, ResolvedSaveFiles};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FileChange {
+    Added,
+    Removed,
+    Modified,
+}
+
+#[derive(Debug)]
+pub struct FileDiff {
+    pub name: String,
+    pub change: FileChange,
+    pub old: Option<GameSaveFileMetadata>,
+    pub new: Option<GameSaveFileMetadata>,
+}
+
+fn file_name((definition_path, file, _): &ResolvedSaveFile) -> String {
+    if file.as_str().is_empty() {
+        definition_path.clone()
+    } else {
+        format!("{}/{}", definition_path.trim_end_matches('/'), file)
+    }
+}
+
+pub fn diff_files(old: &ResolvedSaveFiles, new: &ResolvedSaveFiles) -> Vec<FileDiff> {
+    let mut paths: Vec<&PathBuf> = old.keys().chain(new.keys()).collect();
+    paths.sort();
+    paths.dedup();
+    let mut diffs = vec![];
+    for path in paths {
+        let (name, change) = match (old.get(path), new.get(path)) {
+            (Some(old_file), Some(new_file)) if old_file.2.same_content(&new_file.2) => continue,
+            (Some(old_file), Some(_)) => (file_name(old_file), FileChange::Modified),
+            (Some(old_file), None) => (file_name(old_file), FileChange::Removed),
+            (None, Some(new_file)) => (file_name(new_file), FileChange::Added),
+            (None, None) => unreachable!("paths come from one of the two sides"),
+        };
+        diffs.push(FileDiff {
+            name,
+            change,
+            old: old.get(path).map(|(_, _, metadata)| metadata.clone()),
+            new: new.get(path).map(|(_, _, metadata)| metadata.clone()),
+        });
+    }
+    diffs
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SaveComparison {
+    LocalSinceSync,
+    RepositorySinceSync,
+    // Before the first sync on this device there is nothing else to compare with
+    LocalToRepository,
+}
+
+#[derive(Debug)]
+pub struct SaveDiff {
+    pub comparison: SaveComparison,
+    pub files: Vec<FileDiff>,
+}
+
+pub fn diff_save_state(
+    local_files: &ResolvedSaveFiles,
+    synced_files: Option<&ResolvedSaveFiles>,
+    repository_files: &ResolvedSaveFiles,
+) -> Vec<SaveDiff> {
+    match synced_files {
+        Some(synced_files) => vec![
+            SaveDiff {
+                comparison: SaveComparison::LocalSinceSync,
+                files: diff_files(synced_files, local_files),
+            },
+            SaveDiff {
+                comparison: SaveComparison::RepositorySinceSync,
+                files: diff_files(synced_files, repository_files),
+            },
+        ],
+        None => vec![SaveDiff {
+            comparison: SaveComparison::LocalToRepository,
+            files: diff_files(repository_files, local_files),
+        }],
+    }
+}
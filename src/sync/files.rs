@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Error, Result};
+use relative_path::{PathExt, RelativePathBuf};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    games::{
+        definition::GameDefinition,
+        manifest::{GameSaveFileMetadata, GameSaveManifest},
+        paths,
+    },
+    utils::files::is_temp_path,
+};
+
+pub type ResolvedSaveFiles = HashMap<PathBuf, ResolvedSaveFile>;
+
+pub type ResolvedSaveFile = (String, RelativePathBuf, GameSaveFileMetadata);
+
+pub fn save_file_equal(left: Option<&ResolvedSaveFile>, right: Option<&ResolvedSaveFile>) -> bool {
+    match (left, right) {
+        (Some((_, _, left_metadata)), Some((_, _, right_metadata))) => {
+            left_metadata.same_content(right_metadata)
+        }
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+pub fn save_files_equal(left: &ResolvedSaveFiles, right: &ResolvedSaveFiles) -> bool {
+    if left.len() != right.len() {
+        return false;
+    }
+    left.iter()
+        .all(|(path, left_file)| save_file_equal(Some(left_file), right.get(path)))
+}
+
+pub fn hash_file(path: &Path) -> Result<String> {
+    let mut file =
+        std::fs::File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)
+        .with_context(|| format!("failed to hash {}", path.display()))?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+pub fn get_local_files(
+    definition: &GameDefinition,
+    cached_files: Option<&ResolvedSaveFiles>,
+) -> Result<ResolvedSaveFiles> {
+    let mut files = HashMap::new();
+    for definition_path in &definition.paths {
+        let root_path = paths::rewrite_path(&definition_path.path, definition)?;
+        let filter = definition_path.filter()?;
+        let mut queue = vec![root_path.clone()];
+        while let Some(path) = queue.pop() {
+            if files.contains_key(&path) {
+                continue;
+            }
+            if is_temp_path(&path) {
+                continue;
+            }
+            if path.is_dir() {
+                for item in path.read_dir()? {
+                    queue.push(item?.path());
+                }
+            } else if path.is_file() {
+                let relative_path = path.relative_to(&root_path)?;
+                if !filter.matches(&relative_path) {
+                    continue;
+                }
+                let metadata = path.metadata()?;
+                let modified = time::UtcDateTime::from(metadata.modified()?);
+                let size = metadata.len();
+                let cached_hash = cached_files
+                    .and_then(|cached_files| cached_files.get(&path))
+                    .filter(|(_, _, cached)| cached.modified == modified && cached.size == size)
+                    .and_then(|(_, _, cached)| cached.hash.clone());
+                let hash = match cached_hash {
+                    Some(hash) => hash,
+                    None => hash_file(&path)?,
+                };
+                files.insert(
+                    path,
+                    (
+                        definition_path.path.clone(),
+                        relative_path,
+                        GameSaveFileMetadata {
+                            modified,
+                            size,
+                            hash: Some(hash),
+                        },
+                    ),
+                );
+            } else if path.exists() {
+                return Err(Error::msg(format!(
+                    "save path {} is not a file or directory",
+                    path.display()
+                )));
+            }
+        }
+    }
+    Ok(files)
+}
+
+// Filtering with the given definition keeps old manifests comparable after its globs change
+pub fn get_manifest_files(
+    manifest: &GameSaveManifest,
+    definition: &GameDefinition,
+) -> Result<ResolvedSaveFiles> {
+    let mut files = HashMap::new();
+    for (definition_path, path_files) in &manifest.files {
+        let resolved_path = paths::rewrite_path(definition_path, &manifest.definition)?;
+        let filter = match definition
+            .paths
+            .iter()
+            .find(|path| &path.path == definition_path)
+        {
+            Some(path) => Some(path.filter()?),
+            None => None,
+        };
+        for (file_path, file_metadata) in path_files {
+            if let Some(filter) = &filter
+                && !filter.matches(file_path)
+            {
+                continue;
+            }
+            files.insert(
+                file_path.to_path(&resolved_path),
+                (
+                    definition_path.clone(),
+                    file_path.clone(),
+                    file_metadata.clone(),
+                ),
+            );
+        }
+    }
+    Ok(files)
+}
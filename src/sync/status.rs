@@ -0,0 +1,104 @@
+use anyhow::Result;
+use serde::Serialize;
+use time::OffsetDateTime;
+
+use crate::{
+    games::{
+        definition::{GameDefinition, load_definition},
+        manifest::{read_repository_manifest, read_synced_manifest},
+        paths::rewrite_path,
+    },
+    repository::Repository,
+};
+
+use super::{SaveChanges, classify_file_changes, detect_changes, load_save_state};
+
+#[derive(Clone, Copy, PartialEq, Serialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum GameStatus {
+    InSync,
+    LocalAhead,
+    RepositoryAhead,
+    WillMerge,
+    Conflict,
+    NeverSynced,
+    SavePathMissing,
+}
+
+impl std::fmt::Display for GameStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            GameStatus::InSync => write!(f, "In sync"),
+            GameStatus::LocalAhead => write!(f, "Local ahead"),
+            GameStatus::RepositoryAhead => write!(f, "Repository ahead"),
+            GameStatus::WillMerge => write!(f, "Will merge"),
+            GameStatus::Conflict => write!(f, "Conflict"),
+            GameStatus::NeverSynced => write!(f, "Never synced"),
+            GameStatus::SavePathMissing => write!(f, "Save path missing"),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct GameStatusEntry {
+    pub game: String,
+    pub status: GameStatus,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub synced_here: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub repository_updated: Option<OffsetDateTime>,
+    pub repository_device: Option<String>,
+}
+
+fn save_paths_missing(definition: &GameDefinition) -> bool {
+    let mut resolved = vec![];
+    for path in &definition.paths {
+        match rewrite_path(&path.path, definition) {
+            Ok(path) => resolved.push(path),
+            Err(_) => return true,
+        }
+    }
+    !resolved.iter().any(|path| path.exists())
+}
+
+pub fn game_status(game: &str, repository: &impl Repository) -> Result<GameStatusEntry> {
+    let definition = load_definition(repository, game)?;
+    let status = if save_paths_missing(&definition) {
+        GameStatus::SavePathMissing
+    } else {
+        let state = load_save_state(&definition, repository)?;
+        match (&state.repository, &state.synced) {
+            (Some((repository_files, _)), Some((synced_files, _))) => {
+                match detect_changes(&state.local_files, synced_files, repository_files) {
+                    SaveChanges::Unchanged => GameStatus::InSync,
+                    SaveChanges::LocalChanged => GameStatus::LocalAhead,
+                    SaveChanges::RepositoryChanged => GameStatus::RepositoryAhead,
+                    // Changes to different files are merged without asking
+                    SaveChanges::BothChanged
+                        if classify_file_changes(
+                            &state.local_files,
+                            synced_files,
+                            repository_files,
+                        )
+                        .conflicts
+                        .is_empty() =>
+                    {
+                        GameStatus::WillMerge
+                    }
+                    SaveChanges::BothChanged => GameStatus::Conflict,
+                }
+            }
+            _ => GameStatus::NeverSynced,
+        }
+    };
+    let repository_manifest = read_repository_manifest(repository, game)?;
+    Ok(GameStatusEntry {
+        game: game.into(),
+        status,
+        synced_here: read_synced_manifest(game)?.map(|manifest| manifest.timestamp),
+        repository_updated: repository_manifest
+            .as_ref()
+            .map(|manifest| manifest.timestamp),
+        repository_device: repository_manifest.and_then(|manifest| manifest.device),
+    })
+}
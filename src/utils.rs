@@ -1,2 +1,5 @@
+pub mod clock;
 pub mod config;
+pub mod device;
+pub mod files;
 pub mod paths;
@@ -0,0 +1,6 @@
+use time::OffsetDateTime;
+
+pub fn now() -> OffsetDateTime {
+    // The local offset cannot be read once other threads exist, like under the test harness
+    OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc())
+}
@@ -1,9 +1,13 @@
-use std::{fs, io::ErrorKind::NotFound};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::ErrorKind::NotFound,
+};
 
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 
-use super::paths::config_dir;
+use super::{files::write_private_file_atomic, paths::config_dir};
 use crate::repository::AnyRepositoryConfig;
 
 const CONFIG_FILE: &str = "config.toml";
@@ -11,6 +15,11 @@ const CONFIG_FILE: &str = "config.toml";
 #[derive(Serialize, Deserialize, Default, Debug)]
 pub struct Config {
     pub repository: Option<AnyRepositoryConfig>,
+    #[serde(default)]
+    pub linked_games: BTreeSet<String>,
+    // Each device has its own Wine prefixes, so they are kept out of the shared definitions
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub wine_prefixes: BTreeMap<String, String>,
 }
 
 pub fn load() -> Result<Config> {
@@ -32,6 +41,7 @@ pub fn save(config: &Config) -> Result<()> {
     }
     let serialized =
         toml::to_string_pretty(config).with_context(|| "failed to serialize config")?;
-    fs::write(path, serialized).with_context(|| "failed to write config")?;
+    write_private_file_atomic(&path, &mut serialized.as_bytes())
+        .with_context(|| "failed to write config")?;
     Ok(())
 }
@@ -0,0 +1,5 @@
+use gethostname::gethostname;
+
+pub fn device_name() -> String {
+    gethostname().to_string_lossy().into_owned()
+}
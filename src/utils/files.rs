@@ -0,0 +1,159 @@
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{Context, Error, Result};
+use uuid::Uuid;
+
+pub const TEMP_SUFFIX: &str = ".gamesavesync-tmp";
+
+// Every write gets its own temporary file, so writes to the same path can't mix their content
+fn temp_path(path: &Path) -> Result<PathBuf> {
+    let file_name = path
+        .file_name()
+        .ok_or(Error::msg(format!("{} is not a file path", path.display())))?;
+    let mut temp_name = file_name.to_os_string();
+    temp_name.push(format!(".{}{TEMP_SUFFIX}", Uuid::new_v4().simple()));
+    Ok(path.with_file_name(temp_name))
+}
+
+// Clears the temporary files left next to a path by writes that never finished
+pub fn remove_temp_files(path: &Path) -> Result<()> {
+    let (Some(parent), Some(file_name)) = (path.parent(), path.file_name()) else {
+        return Err(Error::msg(format!("{} is not a file path", path.display())));
+    };
+    if !parent.is_dir() {
+        return Ok(());
+    }
+    let prefix = format!("{}.", file_name.to_string_lossy());
+    for item in parent.read_dir()? {
+        let temp = item?.path();
+        if is_temp_path(&temp)
+            && temp
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with(&prefix))
+        {
+            std::fs::remove_file(&temp)
+                .with_context(|| format!("failed to remove {}", temp.display()))?;
+        }
+    }
+    Ok(())
+}
+
+pub fn is_temp_path(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().ends_with(TEMP_SUFFIX))
+}
+
+pub fn write_file_atomic(
+    path: &Path,
+    content: &mut impl Read,
+    modified: Option<SystemTime>,
+) -> Result<()> {
+    write_atomic(path, content, modified, false)
+}
+
+// Only the current user can read the file, for files that can hold credentials
+pub fn write_private_file_atomic(path: &Path, content: &mut impl Read) -> Result<()> {
+    write_atomic(path, content, None, true)
+}
+
+fn write_atomic(
+    path: &Path,
+    content: &mut impl Read,
+    modified: Option<SystemTime>,
+    private: bool,
+) -> Result<()> {
+    let parent = match path.parent() {
+        Some(parent) => parent,
+        None => unreachable!("written file paths must have a parent"),
+    };
+    std::fs::create_dir_all(parent)
+        .with_context(|| format!("failed to create {}", parent.display()))?;
+    let temp = temp_path(path)?;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    // Set when the file is created, so nobody else can open it before it is private
+    #[cfg(unix)]
+    if private {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(&temp)
+        .with_context(|| format!("failed to create {}", temp.display()))?;
+    let written = write_temp_file(&mut file, &temp, content, modified);
+    drop(file);
+    if let Err(error) = written {
+        let _ = std::fs::remove_file(&temp);
+        return Err(error);
+    }
+    std::fs::rename(&temp, path)
+        .with_context(|| format!("failed to replace {}", path.display()))?;
+    // Make sure the rename itself survives a crash
+    #[cfg(unix)]
+    std::fs::File::open(parent)
+        .and_then(|dir| dir.sync_all())
+        .with_context(|| format!("failed to sync {}", parent.display()))?;
+    Ok(())
+}
+
+fn write_temp_file(
+    file: &mut std::fs::File,
+    temp: &Path,
+    content: &mut impl Read,
+    modified: Option<SystemTime>,
+) -> Result<()> {
+    std::io::copy(content, file).with_context(|| format!("failed to write {}", temp.display()))?;
+    file.flush()?;
+    if let Some(modified) = modified {
+        file.set_modified(modified)
+            .with_context(|| format!("failed to set modified time on {}", temp.display()))?;
+    }
+    file.sync_all()
+        .with_context(|| format!("failed to sync {}", temp.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temp_paths_are_unique() {
+        let path = Path::new("/saves/slot1.sav");
+        let first = temp_path(path).unwrap();
+        assert_ne!(first, temp_path(path).unwrap());
+        assert!(is_temp_path(&first));
+        assert_eq!(first.parent(), path.parent());
+    }
+
+    #[test]
+    fn leftover_temp_files_are_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("slot1.sav");
+        let other = dir.path().join("slot10.sav");
+        write_file_atomic(&path, &mut "level 1".as_bytes(), None).unwrap();
+        let leftover = temp_path(&path).unwrap();
+        let other_leftover = temp_path(&other).unwrap();
+        std::fs::write(&leftover, "partly written").unwrap();
+        std::fs::write(&other_leftover, "partly written").unwrap();
+        remove_temp_files(&path).unwrap();
+        assert!(!leftover.exists());
+        assert!(other_leftover.exists());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "level 1");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn private_files_are_only_readable_by_the_user() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        write_private_file_atomic(&path, &mut "secret".as_bytes()).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
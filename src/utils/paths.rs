@@ -1,8 +1,36 @@
+use std::cell::RefCell;
 use std::path::PathBuf;
 
 use anyhow::{Error, Result};
 use directories::ProjectDirs;
 
+#[derive(Clone, Debug)]
+pub struct DirOverrides {
+    pub home: PathBuf,
+    pub config: PathBuf,
+    pub data: PathBuf,
+}
+
+thread_local! {
+    // Kept per thread so tests running in parallel can each use their own directories
+    static DIR_OVERRIDES: RefCell<Option<DirOverrides>> = const { RefCell::new(None) };
+}
+
+pub fn override_dirs(overrides: Option<DirOverrides>) {
+    DIR_OVERRIDES.with_borrow_mut(|current| *current = overrides);
+}
+
+fn dir_overrides() -> Option<DirOverrides> {
+    DIR_OVERRIDES.with_borrow(|overrides| overrides.clone())
+}
+
+pub fn home_dir() -> Result<PathBuf> {
+    if let Some(overrides) = dir_overrides() {
+        return Ok(overrides.home);
+    }
+    std::env::home_dir().ok_or(Error::msg("failed to get home directory"))
+}
+
 fn project_dirs() -> Result<ProjectDirs> {
     match ProjectDirs::from("org", "GameSaveSync", "GameSaveSync") {
         Some(dirs) => Ok(dirs),
@@ -11,12 +39,18 @@ fn project_dirs() -> Result<ProjectDirs> {
 }
 
 pub fn config_dir() -> Result<PathBuf> {
+    if let Some(overrides) = dir_overrides() {
+        return Ok(overrides.config);
+    }
     let dirs = project_dirs()?;
     let path = dirs.config_local_dir();
     Ok(path.to_path_buf())
 }
 
 pub fn data_dir() -> Result<PathBuf> {
+    if let Some(overrides) = dir_overrides() {
+        return Ok(overrides.data);
+    }
     let dirs = project_dirs()?;
     let path = dirs.data_local_dir();
     Ok(path.to_path_buf())
@@ -0,0 +1,151 @@
+mod common;
+
+use std::path::Path;
+
+use common::{
+    GAME, ScriptedResolver, TestDevice,
+    contract::{assert_file_operations, assert_sync_between_devices},
+    prepare_test_repository,
+};
+use gamesavesync::{
+    games::{
+        GamePlatform,
+        definition::{GameDefinition, GameDefinitionPath, write_definition},
+    },
+    repository::{
+        Repository, RepositoryExt,
+        archive_repository::{
+            ArchiveRepository, ArchiveRepositoryConfig, create_archive, open_repository,
+        },
+    },
+    sync::{SyncAction, callbacks::ConflictResolution},
+};
+use relative_path::RelativePath;
+
+fn open(path: &Path) -> ArchiveRepository {
+    open_repository(&ArchiveRepositoryConfig { path: path.into() }).unwrap()
+}
+
+fn sync_through_archive(file_name: &str) {
+    let root = tempfile::tempdir().unwrap();
+    let path = root.path().join(file_name);
+    create_archive(&path).unwrap();
+    // Both devices reopen the archive, so the first one's changes have to be pushed
+    assert_sync_between_devices(|| open(&path));
+}
+
+#[test]
+fn archive_file_operations() {
+    let root = tempfile::tempdir().unwrap();
+    let path = root.path().join("saves.zip");
+    create_archive(&path).unwrap();
+    assert_file_operations(&open(&path));
+}
+
+#[test]
+fn zip_archive_syncs_between_devices() {
+    sync_through_archive("saves.zip");
+}
+
+#[test]
+fn tar_archive_syncs_between_devices() {
+    sync_through_archive("saves.tar");
+}
+
+#[test]
+fn archive_is_rewritten_when_pushed() {
+    let root = tempfile::tempdir().unwrap();
+    let path = root.path().join("saves.zip");
+    create_archive(&path).unwrap();
+    let empty = std::fs::read(&path).unwrap();
+    let repository = open(&path);
+    let file = RelativePath::new(GAME).join("blobs/a file.bin");
+    repository.write_string(&file, "save data").unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), empty);
+    repository.close().unwrap();
+    let archive = zip::ZipArchive::new(std::fs::File::open(&path).unwrap()).unwrap();
+    assert_eq!(
+        archive.file_names().collect::<Vec<_>>(),
+        vec![format!("{GAME}/blobs/a file.bin")]
+    );
+    repository.remove(RelativePath::new(GAME)).unwrap();
+    drop(repository);
+    // Changes that were never pushed are left out of the archive
+    let repository = open(&path);
+    assert!(repository.is_file(&file).unwrap());
+    repository.remove(RelativePath::new(GAME)).unwrap();
+    repository.push().unwrap();
+    let repository = open(&path);
+    assert!(!repository.is_file(&file).unwrap());
+    assert!(
+        repository
+            .read_dir(RelativePath::new(""))
+            .unwrap()
+            .next()
+            .is_none()
+    );
+    let leftovers: Vec<_> = std::fs::read_dir(root.path()).unwrap().collect();
+    assert_eq!(leftovers.len(), 1);
+}
+
+#[test]
+fn games_synced_before_a_failure_are_pushed() {
+    let root = tempfile::tempdir().unwrap();
+    let path = root.path().join("saves.zip");
+    create_archive(&path).unwrap();
+    let repository = open(&path);
+    prepare_test_repository(&repository);
+    // Sorts after the test game and fails because its path covers the whole home directory
+    let broken = "Unsyncable Game";
+    write_definition(
+        &repository,
+        &GameDefinition {
+            name: broken.into(),
+            platform: GamePlatform::Linux,
+            paths: vec![GameDefinitionPath::new("<home>".into())],
+            steam_app_id: None,
+            conflict_policy: None,
+        },
+    )
+    .unwrap();
+    repository.push().unwrap();
+
+    let device = TestDevice::new();
+    device.write_save("slot1.sav", "level 1");
+    assert!(
+        device
+            .sync_games(
+                &open(&path),
+                &[GAME, broken],
+                &mut ScriptedResolver::default()
+            )
+            .is_err()
+    );
+    let report = device
+        .sync_linked(&open(&path), &mut ScriptedResolver::default())
+        .unwrap();
+    assert_eq!(report.action, SyncAction::UpToDate);
+    assert_eq!(device.read_save("slot1.sav").as_deref(), Some("level 1"));
+
+    let other = TestDevice::new();
+    let report = other
+        .sync_linked(
+            &open(&path),
+            &mut ScriptedResolver::new(&[ConflictResolution::UseRepository { keep_other: false }]),
+        )
+        .unwrap();
+    assert_eq!(report.action, SyncAction::Applied);
+    assert_eq!(other.read_save("slot1.sav").as_deref(), Some("level 1"));
+}
+
+#[test]
+fn archive_needs_known_extension() {
+    let root = tempfile::tempdir().unwrap();
+    assert!(create_archive(&root.path().join("saves.rar")).is_err());
+    assert!(
+        open_repository(&ArchiveRepositoryConfig {
+            path: root.path().join("missing.zip"),
+        })
+        .is_err()
+    );
+}
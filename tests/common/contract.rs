@@ -0,0 +1,87 @@
+use std::io::Read;
+
+use gamesavesync::{
+    repository::{Repository, RepositoryExt},
+    sync::{SyncAction, callbacks::ConflictResolution},
+};
+use relative_path::{RelativePath, RelativePathBuf};
+
+use super::{ScriptedResolver, TestDevice, prepare_test_repository};
+
+// What every backend has to do the same way, starting from an empty repository
+pub fn assert_file_operations(repository: &impl Repository) {
+    let path = RelativePath::new("Some Game/blobs/a file.bin");
+    assert!(!repository.is_file(path).unwrap());
+    repository.write_string(path, "save data").unwrap();
+    assert!(repository.is_file(path).unwrap());
+    assert!(
+        repository
+            .is_dir(RelativePath::new("Some Game/blobs"))
+            .unwrap()
+    );
+    assert!(!repository.is_dir(path).unwrap());
+    let mut content = String::new();
+    repository
+        .read_file(path)
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    assert_eq!(content, "save data");
+    repository.write_string(path, "newer save data").unwrap();
+    assert_eq!(repository.read_string(path).unwrap(), "newer save data");
+
+    assert!(
+        !repository
+            .create_file(path, &mut "other save data".as_bytes())
+            .unwrap()
+    );
+    assert_eq!(repository.read_string(path).unwrap(), "newer save data");
+    let created = RelativePath::new("Some Game/lock.toml");
+    assert!(
+        repository
+            .create_file(created, &mut "held".as_bytes())
+            .unwrap()
+    );
+    assert_eq!(repository.read_string(created).unwrap(), "held");
+
+    let mut entries: Vec<RelativePathBuf> = repository
+        .read_dir(RelativePath::new("Some Game"))
+        .unwrap()
+        .collect::<anyhow::Result<_>>()
+        .unwrap();
+    entries.sort();
+    assert_eq!(
+        entries,
+        vec![
+            RelativePathBuf::from("blobs"),
+            RelativePathBuf::from("lock.toml")
+        ]
+    );
+    repository.remove(RelativePath::new("Some Game")).unwrap();
+    assert!(!repository.is_file(path).unwrap());
+    assert!(!repository.is_dir(RelativePath::new("Some Game")).unwrap());
+}
+
+// Each device opens the repository for itself, the way separate machines would
+pub fn assert_sync_between_devices<R: Repository>(open: impl Fn() -> R) {
+    let first = TestDevice::new();
+    let second = TestDevice::new();
+    first.write_save("slot1.sav", "level 1");
+    {
+        let repository = open();
+        prepare_test_repository(&repository);
+        let report = first
+            .sync_linked(&repository, &mut ScriptedResolver::default())
+            .unwrap();
+        assert_eq!(report.action, SyncAction::Stored);
+    }
+    let repository = open();
+    let report = second
+        .sync_linked(
+            &repository,
+            &mut ScriptedResolver::new(&[ConflictResolution::UseRepository { keep_other: false }]),
+        )
+        .unwrap();
+    assert_eq!(report.action, SyncAction::Applied);
+    assert_eq!(second.read_save("slot1.sav").as_deref(), Some("level 1"));
+}
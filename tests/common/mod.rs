@@ -0,0 +1,203 @@
+#![allow(dead_code)]
+
+use std::collections::VecDeque;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use gamesavesync::{
+    games::{
+        ConflictPolicy, GamePlatform,
+        definition::{GameDefinition, GameDefinitionPath, write_definition},
+    },
+    repository::{
+        AnyRepositoryConfig, Repository,
+        memory_repository::MemoryRepository,
+        settings::{RepositorySettings, write_settings},
+    },
+    sync::{
+        SyncOptions, SyncReport,
+        callbacks::{Conflict, ConflictReason, ConflictResolution, ConflictResolver, SyncEvent},
+        sync_game, sync_games,
+    },
+    utils::{
+        config::{self, Config},
+        paths::{DirOverrides, config_dir, override_dirs},
+    },
+};
+use tempfile::TempDir;
+
+pub mod contract;
+pub mod http;
+pub mod s3;
+pub mod webdav;
+
+pub const GAME: &str = "TestGame";
+
+pub fn test_repository() -> MemoryRepository {
+    let repository = MemoryRepository::new();
+    prepare_test_repository(&repository);
+    repository
+}
+
+pub fn prepare_test_repository(repository: &impl Repository) {
+    write_settings(repository, &RepositorySettings::default()).unwrap();
+    write_definition(
+        repository,
+        &GameDefinition {
+            name: GAME.into(),
+            platform: GamePlatform::Linux,
+            paths: vec![GameDefinitionPath::new("<home>/saves".into())],
+            steam_app_id: None,
+            conflict_policy: None,
+        },
+    )
+    .unwrap();
+}
+
+// Saves the repository to a fresh config file and returns what was written
+pub fn saved_config(repository: AnyRepositoryConfig) -> String {
+    let device = TestDevice::new();
+    device.activate();
+    config::save(&Config {
+        repository: Some(repository),
+        ..Default::default()
+    })
+    .unwrap();
+    let path = config_dir().unwrap().join("config.toml");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+    std::fs::read_to_string(path).unwrap()
+}
+
+// A device with its own home and data directories, made current by each call into it
+pub struct TestDevice {
+    _root: TempDir,
+    dirs: DirOverrides,
+}
+
+impl TestDevice {
+    pub fn new() -> Self {
+        let root = tempfile::tempdir().unwrap();
+        let dirs = DirOverrides {
+            home: root.path().join("home"),
+            config: root.path().join("config"),
+            data: root.path().join("data"),
+        };
+        std::fs::create_dir_all(dirs.home.join("saves")).unwrap();
+        TestDevice { _root: root, dirs }
+    }
+
+    pub fn activate(&self) {
+        override_dirs(Some(self.dirs.clone()));
+    }
+
+    pub fn save_path(&self, file: &str) -> PathBuf {
+        self.dirs.home.join("saves").join(file)
+    }
+
+    pub fn write_save(&self, file: &str, content: &str) {
+        std::fs::write(self.save_path(file), content).unwrap();
+    }
+
+    pub fn read_save(&self, file: &str) -> Option<String> {
+        std::fs::read_to_string(self.save_path(file)).ok()
+    }
+
+    pub fn remove_save(&self, file: &str) {
+        std::fs::remove_file(self.save_path(file)).unwrap();
+    }
+
+    pub fn sync(
+        &self,
+        repository: &impl Repository,
+        resolver: &mut ScriptedResolver,
+    ) -> Result<SyncReport> {
+        self.activate();
+        sync_game(
+            GAME,
+            repository,
+            &SyncOptions::default(),
+            resolver,
+            &mut |_: &SyncEvent| {},
+        )
+    }
+
+    pub fn sync_with_policy(
+        &self,
+        repository: &impl Repository,
+        policy: ConflictPolicy,
+    ) -> Result<SyncReport> {
+        self.activate();
+        sync_game(
+            GAME,
+            repository,
+            &SyncOptions {
+                on_conflict: Some(policy),
+                ..Default::default()
+            },
+            &mut ScriptedResolver::default(),
+            &mut |_: &SyncEvent| {},
+        )
+    }
+
+    // Syncs the way the sync command does, including the repository's pull and push
+    pub fn sync_linked(
+        &self,
+        repository: &impl Repository,
+        resolver: &mut ScriptedResolver,
+    ) -> Result<SyncReport> {
+        let mut reports = self.sync_games(repository, &[GAME], resolver)?;
+        Ok(reports.remove(0))
+    }
+
+    pub fn sync_games(
+        &self,
+        repository: &impl Repository,
+        games: &[&str],
+        resolver: &mut ScriptedResolver,
+    ) -> Result<Vec<SyncReport>> {
+        self.activate();
+        let config = Config {
+            linked_games: games.iter().map(|game| game.to_string()).collect(),
+            ..Default::default()
+        };
+        sync_games(
+            repository,
+            &config,
+            None,
+            &SyncOptions::default(),
+            resolver,
+            &mut |_: &SyncEvent| {},
+        )
+    }
+}
+
+// Answers conflicts from a list and remembers what it was asked
+#[derive(Default)]
+pub struct ScriptedResolver {
+    resolutions: VecDeque<ConflictResolution>,
+    pub conflicts: Vec<ConflictReason>,
+}
+
+impl ScriptedResolver {
+    pub fn new(resolutions: &[ConflictResolution]) -> Self {
+        ScriptedResolver {
+            resolutions: resolutions.iter().copied().collect(),
+            conflicts: vec![],
+        }
+    }
+}
+
+impl ConflictResolver for ScriptedResolver {
+    fn resolve(&mut self, conflict: &Conflict) -> Result<ConflictResolution> {
+        self.conflicts.push(conflict.reason.clone());
+        Ok(self
+            .resolutions
+            .pop_front()
+            .expect("the sync asked about an unexpected conflict"))
+    }
+}
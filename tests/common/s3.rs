@@ -0,0 +1,314 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Mutex;
+
+use hmac::{Hmac, Mac};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+use sha2::{Digest, Sha256};
+
+use super::http::{self, HttpRequest, HttpResponse};
+
+pub const BUCKET: &str = "saves";
+pub const ACCESS_KEY_ID: &str = "player";
+pub const SECRET_ACCESS_KEY: &str = "secret";
+// Small pages so listings have to follow continuation tokens
+const PAGE_SIZE: usize = 3;
+const MIN_PART_SIZE: usize = 5 * 1024 * 1024;
+const UNRESERVED: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+#[derive(Default)]
+struct Storage {
+    objects: BTreeMap<String, Vec<u8>>,
+    uploads: BTreeMap<String, BTreeMap<usize, Vec<u8>>>,
+    next_upload: usize,
+}
+
+// Just enough of the S3 API to exercise the repository backend, with path-style buckets
+pub struct S3Server {
+    pub endpoint: String,
+    storage: std::sync::Arc<Mutex<Storage>>,
+}
+
+impl S3Server {
+    pub fn start() -> Self {
+        let storage = std::sync::Arc::new(Mutex::new(Storage::default()));
+        let shared = storage.clone();
+        let address = http::start(move |request| {
+            if !is_signed(request) {
+                return error(403, "SignatureDoesNotMatch");
+            }
+            if request.header("x-amz-content-sha256") != Some(sha256_hex(&request.body).as_str()) {
+                return error(400, "XAmzContentSHA256Mismatch");
+            }
+            handle(request, &mut shared.lock().unwrap())
+        });
+        S3Server {
+            endpoint: format!("http://{address}"),
+            storage,
+        }
+    }
+
+    pub fn object_keys(&self) -> Vec<String> {
+        self.storage
+            .lock()
+            .unwrap()
+            .objects
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    pub fn pending_uploads(&self) -> usize {
+        self.storage.lock().unwrap().uploads.len()
+    }
+}
+
+fn sha256_hex(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+fn hmac_sha256(key: &[u8], content: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+    mac.update(content.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn encode(value: &str) -> String {
+    utf8_percent_encode(value, UNRESERVED).to_string()
+}
+
+// Rebuilds the canonical request from what was received and checks the signature against it
+fn is_signed(request: &HttpRequest) -> bool {
+    let Some(authorization) = request
+        .header("Authorization")
+        .and_then(|authorization| authorization.strip_prefix("AWS4-HMAC-SHA256 "))
+    else {
+        return false;
+    };
+    let fields: BTreeMap<&str, &str> = authorization
+        .split(", ")
+        .filter_map(|field| field.split_once('='))
+        .collect();
+    let (Some(credential), Some(header_names), Some(signature)) = (
+        fields.get("Credential"),
+        fields.get("SignedHeaders"),
+        fields.get("Signature"),
+    ) else {
+        return false;
+    };
+    let Some((access_key_id, scope)) = credential.split_once('/') else {
+        return false;
+    };
+    let Some(timestamp) = request.header("x-amz-date") else {
+        return false;
+    };
+    let scope_parts: Vec<&str> = scope.split('/').collect();
+    let [date, region, "s3", "aws4_request"] = scope_parts[..] else {
+        return false;
+    };
+    if access_key_id != ACCESS_KEY_ID || !timestamp.starts_with(date) {
+        return false;
+    }
+    let header_names: Vec<&str> = header_names.split(';').collect();
+    if !["host", "x-amz-content-sha256", "x-amz-date"]
+        .iter()
+        .all(|name| header_names.contains(name))
+    {
+        return false;
+    }
+    let mut canonical_headers = String::new();
+    for name in &header_names {
+        let Some(value) = request.header(name) else {
+            return false;
+        };
+        canonical_headers.push_str(&format!("{name}:{}\n", value.trim()));
+    }
+    let uri: Vec<String> = request.path().split('/').map(encode).collect();
+    let mut query: Vec<(String, String)> = request
+        .query()
+        .iter()
+        .map(|(name, value)| (encode(name), encode(value)))
+        .collect();
+    query.sort();
+    let query: Vec<String> = query
+        .iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect();
+    let canonical_request = format!(
+        "{}\n/{}\n{}\n{canonical_headers}\n{}\n{}",
+        request.method,
+        uri.join("/"),
+        query.join("&"),
+        header_names.join(";"),
+        request.header("x-amz-content-sha256").unwrap_or_default()
+    );
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{timestamp}\n{scope}\n{}",
+        sha256_hex(canonical_request.as_bytes())
+    );
+    let mut signing_key = format!("AWS4{SECRET_ACCESS_KEY}").into_bytes();
+    for part in [date, region, "s3", "aws4_request"] {
+        signing_key = hmac_sha256(&signing_key, part);
+    }
+    let expected: String = hmac_sha256(&signing_key, &string_to_sign)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    *signature == expected
+}
+
+fn error(status: u16, code: &str) -> HttpResponse {
+    HttpResponse::new(
+        status,
+        format!("<Error><Code>{code}</Code><Message>{code}</Message></Error>"),
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn list(request: &HttpRequest, storage: &Storage) -> HttpResponse {
+    let prefix = request.query_value("prefix").unwrap_or_default();
+    let delimiter = request.query_value("delimiter");
+    let max_keys = request
+        .query_value("max-keys")
+        .and_then(|max_keys| max_keys.parse().ok())
+        .unwrap_or(PAGE_SIZE)
+        .min(PAGE_SIZE);
+    let after = request
+        .query_value("continuation-token")
+        .unwrap_or_default();
+    let mut entries = BTreeSet::new();
+    for key in storage.objects.keys() {
+        let Some(rest) = key.strip_prefix(&prefix) else {
+            continue;
+        };
+        match delimiter
+            .as_deref()
+            .and_then(|delimiter| rest.find(delimiter))
+        {
+            Some(end) => entries.insert((format!("{prefix}{}", &rest[..=end]), true)),
+            None => entries.insert((key.clone(), false)),
+        };
+    }
+    let remaining: Vec<_> = entries
+        .into_iter()
+        .filter(|(entry, _)| *entry > after)
+        .collect();
+    let page = &remaining[..remaining.len().min(max_keys)];
+    let mut body = String::from("<ListBucketResult>");
+    for (entry, is_prefix) in page {
+        if *is_prefix {
+            body.push_str(&format!(
+                "<CommonPrefixes><Prefix>{}</Prefix></CommonPrefixes>",
+                escape(entry)
+            ));
+        } else {
+            body.push_str(&format!(
+                "<Contents><Key>{}</Key></Contents>",
+                escape(entry)
+            ));
+        }
+    }
+    let truncated = remaining.len() > page.len();
+    body.push_str(&format!("<IsTruncated>{truncated}</IsTruncated>"));
+    if let (true, Some((last, _))) = (truncated, page.last()) {
+        body.push_str(&format!(
+            "<NextContinuationToken>{}</NextContinuationToken>",
+            escape(last)
+        ));
+    }
+    body.push_str("</ListBucketResult>");
+    HttpResponse::new(200, body)
+}
+
+fn complete_upload(key: String, upload: &str, body: &[u8], storage: &mut Storage) -> HttpResponse {
+    let Some(parts) = storage.uploads.remove(upload) else {
+        return error(404, "NoSuchUpload");
+    };
+    let body = String::from_utf8_lossy(body);
+    let numbers: Vec<usize> = body
+        .split("<PartNumber>")
+        .skip(1)
+        .filter_map(|part| part.split('<').next()?.parse().ok())
+        .collect();
+    let mut content = vec![];
+    for (index, number) in numbers.iter().enumerate() {
+        let Some(part) = parts.get(number) else {
+            return error(400, "InvalidPart");
+        };
+        if index + 1 < numbers.len() && part.len() < MIN_PART_SIZE {
+            return error(400, "EntityTooSmall");
+        }
+        content.extend_from_slice(part);
+    }
+    storage.objects.insert(key, content);
+    HttpResponse::new(200, "<CompleteMultipartUploadResult/>")
+}
+
+fn handle(request: &HttpRequest, storage: &mut Storage) -> HttpResponse {
+    let path = request.path();
+    let (bucket, key) = path.split_once('/').unwrap_or((&path, ""));
+    if bucket != BUCKET {
+        return error(404, "NoSuchBucket");
+    }
+    let key = key.to_string();
+    let upload = request.query_value("uploadId");
+    match (request.method.as_str(), key.is_empty()) {
+        ("GET", true) => list(request, storage),
+        ("HEAD", false) | ("GET", false) => match storage.objects.get(&key) {
+            Some(content) => HttpResponse::new(200, content.clone()),
+            None => error(404, "NoSuchKey"),
+        },
+        ("PUT", false) => match (upload, request.query_value("partNumber")) {
+            (Some(upload), Some(number)) => match storage.uploads.get_mut(&upload) {
+                Some(parts) => {
+                    parts.insert(number.parse().unwrap(), request.body.clone());
+                    HttpResponse::status(200).header("ETag", &format!("\"{number}\""))
+                }
+                None => error(404, "NoSuchUpload"),
+            },
+            _ if request.header("If-None-Match") == Some("*")
+                && storage.objects.contains_key(&key) =>
+            {
+                error(412, "PreconditionFailed")
+            }
+            _ => {
+                storage.objects.insert(key, request.body.clone());
+                HttpResponse::status(200)
+            }
+        },
+        ("POST", false) if request.query_value("uploads").is_some() => {
+            storage.next_upload += 1;
+            let upload = format!("upload-{}", storage.next_upload);
+            storage.uploads.insert(upload.clone(), BTreeMap::new());
+            HttpResponse::new(
+                200,
+                format!(
+                    "<InitiateMultipartUploadResult><UploadId>{upload}</UploadId></InitiateMultipartUploadResult>"
+                ),
+            )
+        }
+        ("POST", false) if upload.is_some() => {
+            complete_upload(key, &upload.unwrap(), &request.body, storage)
+        }
+        ("DELETE", false) => {
+            match upload {
+                Some(upload) => {
+                    storage.uploads.remove(&upload);
+                }
+                None => {
+                    storage.objects.remove(&key);
+                }
+            }
+            HttpResponse::status(204)
+        }
+        _ => error(405, "MethodNotAllowed"),
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Mutex;
+
+use super::http::{self, HttpRequest, HttpResponse, decoded_path};
+
+#[derive(Default)]
+struct Storage {
+    files: BTreeMap<String, Vec<u8>>,
+    collections: BTreeSet<String>,
+}
+
+impl Storage {
+    fn is_collection(&self, path: &str) -> bool {
+        path.is_empty() || self.collections.contains(path)
+    }
+
+    fn children(&self, path: &str) -> Vec<(String, bool)> {
+        let prefix = if path.is_empty() {
+            String::new()
+        } else {
+            format!("{path}/")
+        };
+        let is_child = |child: &&String| {
+            child
+                .strip_prefix(&prefix)
+                .is_some_and(|name| !name.is_empty() && !name.contains('/'))
+        };
+        let files = self
+            .files
+            .keys()
+            .filter(is_child)
+            .map(|file| (file.clone(), false));
+        let collections = self
+            .collections
+            .iter()
+            .filter(is_child)
+            .map(|collection| (collection.clone(), true));
+        files.chain(collections).collect()
+    }
+}
+
+// Just enough of a WebDAV server to exercise the repository backend
+pub struct WebDavServer {
+    pub url: String,
+}
+
+impl WebDavServer {
+    pub fn start(credentials: Option<(&str, &str)>) -> Self {
+        let storage = Mutex::new(Storage::default());
+        storage.lock().unwrap().collections.insert("dav".into());
+        let authorization = credentials.map(|(username, password)| {
+            use base64::Engine;
+            let encoded = base64::prelude::BASE64_STANDARD.encode(format!("{username}:{password}"));
+            format!("Basic {encoded}")
+        });
+        let address = http::start(move |request| match &authorization {
+            Some(expected) if request.header("Authorization") != Some(expected) => {
+                HttpResponse::status(401)
+            }
+            _ => {
+                let (status, body) = handle(request, &storage);
+                HttpResponse::new(status, body)
+            }
+        });
+        WebDavServer {
+            url: format!("http://{address}/dav"),
+        }
+    }
+}
+
+fn parent_path(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(parent, _)| parent)
+}
+
+fn propfind_entry(path: &str, is_collection: bool) -> String {
+    let resource_type = if is_collection { "<d:collection/>" } else { "" };
+    let slash = if is_collection { "/" } else { "" };
+    format!(
+        "<d:response><d:href>/{path}{slash}</d:href><d:propstat><d:prop><d:resourcetype>{resource_type}</d:resourcetype></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>"
+    )
+}
+
+fn handle(request: &HttpRequest, storage: &Mutex<Storage>) -> (u16, Vec<u8>) {
+    let mut storage = storage.lock().unwrap();
+    let path = request.path();
+    match request.method.as_str() {
+        "PROPFIND" => {
+            let mut entries = if storage.files.contains_key(&path) {
+                vec![propfind_entry(&path, false)]
+            } else if storage.is_collection(&path) {
+                vec![propfind_entry(&path, true)]
+            } else {
+                return (404, vec![]);
+            };
+            if request.header("Depth") == Some("1") && storage.is_collection(&path) {
+                for (child, is_collection) in storage.children(&path) {
+                    entries.push(propfind_entry(&child, is_collection));
+                }
+            }
+            let body = format!(
+                r#"<?xml version="1.0" encoding="utf-8"?><d:multistatus xmlns:d="DAV:">{}</d:multistatus>"#,
+                entries.concat()
+            );
+            (207, body.into_bytes())
+        }
+        "GET" => match storage.files.get(&path) {
+            Some(content) => (200, content.clone()),
+            None => (404, vec![]),
+        },
+        "PUT" if !storage.is_collection(parent_path(&path)) => (409, vec![]),
+        "PUT"
+            if request.header("If-None-Match") == Some("*")
+                && storage.files.contains_key(&path) =>
+        {
+            (412, vec![])
+        }
+        "PUT" => {
+            storage.files.insert(path, request.body.clone());
+            (201, vec![])
+        }
+        "MKCOL" if storage.is_collection(&path) || storage.files.contains_key(&path) => {
+            (405, vec![])
+        }
+        "MKCOL" if !storage.is_collection(parent_path(&path)) => (409, vec![]),
+        "MKCOL" => {
+            storage.collections.insert(path);
+            (201, vec![])
+        }
+        "MOVE" => {
+            let destination = match request.header("Destination") {
+                Some(destination) => decoded_path(destination),
+                None => return (400, vec![]),
+            };
+            match storage.files.remove(&path) {
+                Some(content) => {
+                    let replaced = storage.files.insert(destination, content).is_some();
+                    (if replaced { 204 } else { 201 }, vec![])
+                }
+                None => (404, vec![]),
+            }
+        }
+        "DELETE" => {
+            let prefix = format!("{path}/");
+            let existed = storage.files.remove(&path).is_some() | storage.collections.remove(&path);
+            storage.files.retain(|file, _| !file.starts_with(&prefix));
+            storage
+                .collections
+                .retain(|collection| !collection.starts_with(&prefix));
+            (if existed { 204 } else { 404 }, vec![])
+        }
+        _ => (405, vec![]),
+    }
+}
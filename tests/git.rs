@@ -0,0 +1,191 @@
+mod common;
+
+use std::path::Path;
+use std::process::Command;
+
+use common::{GAME, ScriptedResolver, TestDevice, prepare_test_repository};
+use gamesavesync::{
+    repository::{
+        Repository,
+        git_repository::{GitRepository, init_repository, open_repository},
+    },
+    sync::{
+        SyncAction, SyncOptions,
+        callbacks::{ConflictResolution, SyncEvent},
+        sync_games,
+    },
+    utils::{config::Config, device::device_name},
+};
+use relative_path::{RelativePath, RelativePathBuf};
+use tempfile::TempDir;
+
+const USE_REPOSITORY: ConflictResolution = ConflictResolution::UseRepository { keep_other: false };
+
+fn git(path: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(path)
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "git {args:?} failed");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn commit_messages(path: &Path) -> Vec<String> {
+    git(path, &["log", "--format=%s"])
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+fn bare_remote() -> TempDir {
+    let remote = tempfile::tempdir().unwrap();
+    git(remote.path(), &["init", "--quiet", "--bare"]);
+    remote
+}
+
+fn clone(remote: &TempDir) -> (TempDir, GitRepository) {
+    let root = tempfile::tempdir().unwrap();
+    let config = init_repository(
+        &root.path().join("repository"),
+        Some(&remote.path().to_string_lossy()),
+    )
+    .unwrap();
+    let repository = open_repository(&config).unwrap();
+    (root, repository)
+}
+
+#[test]
+fn git_commits_each_manifest_change() {
+    let root = tempfile::tempdir().unwrap();
+    let config = init_repository(root.path(), None).unwrap();
+    assert_eq!(config.remote, None);
+    let repository = open_repository(&config).unwrap();
+    prepare_test_repository(&repository);
+    let entries: Vec<RelativePathBuf> = repository
+        .read_dir(RelativePath::new(""))
+        .unwrap()
+        .collect::<anyhow::Result<_>>()
+        .unwrap();
+    assert!(
+        !entries
+            .iter()
+            .any(|entry| entry.as_str().starts_with(".git"))
+    );
+    let device = TestDevice::new();
+    device.write_save("slot1.sav", "level 1");
+    let report = device
+        .sync_linked(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    assert_eq!(report.action, SyncAction::Stored);
+    device.write_save("slot1.sav", "level 2 reached");
+    device
+        .sync_linked(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    let device_name = device_name();
+    assert_eq!(
+        commit_messages(root.path()),
+        vec![
+            format!("Store {GAME} from {device_name}"),
+            format!("Store {GAME} from {device_name}"),
+            format!("Update repository from {device_name}"),
+        ]
+    );
+    assert!(git(root.path(), &["status", "--porcelain"]).is_empty());
+}
+
+#[test]
+fn git_dry_run_does_not_commit() {
+    let root = tempfile::tempdir().unwrap();
+    let repository = open_repository(&init_repository(root.path(), None).unwrap()).unwrap();
+    prepare_test_repository(&repository);
+    let device = TestDevice::new();
+    device.write_save("slot1.sav", "level 1");
+    device.activate();
+    let reports = sync_games(
+        &repository,
+        &Config {
+            linked_games: [GAME.to_string()].into(),
+            ..Default::default()
+        },
+        None,
+        &SyncOptions {
+            dry_run: true,
+            ..SyncOptions::default()
+        },
+        &mut ScriptedResolver::default(),
+        &mut |_: &SyncEvent| {},
+    )
+    .unwrap();
+    assert_eq!(reports[0].action, SyncAction::Stored);
+    assert!(
+        !Command::new("git")
+            .arg("-C")
+            .arg(root.path())
+            .args(["rev-parse", "--verify", "--quiet", "HEAD"])
+            .status()
+            .unwrap()
+            .success()
+    );
+}
+
+#[test]
+fn git_syncs_devices_through_remote() {
+    let remote = bare_remote();
+    let (_first_root, first_repository) = clone(&remote);
+    prepare_test_repository(&first_repository);
+    let first = TestDevice::new();
+    first.write_save("slot1.sav", "level 1");
+    first
+        .sync_linked(&first_repository, &mut ScriptedResolver::default())
+        .unwrap();
+    assert_eq!(commit_messages(remote.path()).len(), 2);
+
+    let (_second_root, second_repository) = clone(&remote);
+    let second = TestDevice::new();
+    let report = second
+        .sync_linked(
+            &second_repository,
+            &mut ScriptedResolver::new(&[USE_REPOSITORY]),
+        )
+        .unwrap();
+    assert_eq!(report.action, SyncAction::Applied);
+    assert_eq!(second.read_save("slot1.sav").as_deref(), Some("level 1"));
+
+    second.write_save("slot1.sav", "level 2 on second");
+    second
+        .sync_linked(&second_repository, &mut ScriptedResolver::default())
+        .unwrap();
+    let report = first
+        .sync_linked(&first_repository, &mut ScriptedResolver::default())
+        .unwrap();
+    assert_eq!(report.action, SyncAction::Applied);
+    assert_eq!(
+        first.read_save("slot1.sav").as_deref(),
+        Some("level 2 on second")
+    );
+    assert_eq!(commit_messages(remote.path()).len(), 3);
+}
+
+#[test]
+fn git_push_catches_up_with_other_devices() {
+    let remote = bare_remote();
+    let (_first_root, first_repository) = clone(&remote);
+    prepare_test_repository(&first_repository);
+    first_repository.push().unwrap();
+    let (second_root, second_repository) = clone(&remote);
+
+    let first = TestDevice::new();
+    first.write_save("slot1.sav", "level 1");
+    first
+        .sync_linked(&first_repository, &mut ScriptedResolver::default())
+        .unwrap();
+    // A change made on the second device before it has seen the first device's sync
+    std::fs::write(second_root.path().join("repository/notes.txt"), "notes").unwrap();
+    second_repository.push().unwrap();
+    let log = commit_messages(remote.path());
+    assert_eq!(log.len(), 3);
+    assert!(log[0].starts_with("Update repository"));
+    assert!(log[1].starts_with(&format!("Store {GAME}")));
+}
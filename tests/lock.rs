@@ -0,0 +1,132 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{GAME, test_repository};
+use gamesavesync::repository::{
+    Repository, RepositoryExt,
+    lock::{LockEvent, LockHolder, acquire_lock},
+    memory_repository::MemoryRepository,
+};
+use relative_path::{RelativePath, RelativePathBuf};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+fn lock_path() -> RelativePathBuf {
+    RelativePath::new(GAME).join("lock.toml")
+}
+
+fn read_holder(repository: &impl Repository) -> Option<LockHolder> {
+    let path = lock_path();
+    repository
+        .is_file(&path)
+        .unwrap()
+        .then(|| toml::from_str(&repository.read_string(&path).unwrap()).unwrap())
+}
+
+fn write_holder(repository: &impl Repository, holder: &LockHolder) {
+    repository
+        .write_string(&lock_path(), &toml::to_string(holder).unwrap())
+        .unwrap();
+}
+
+fn other_device(timestamp: OffsetDateTime) -> LockHolder {
+    LockHolder {
+        token: Uuid::new_v4(),
+        device: "another device".into(),
+        pid: 1,
+        timestamp,
+    }
+}
+
+#[test]
+fn create_file_does_not_replace_existing_files() {
+    let repository = MemoryRepository::new();
+    let path = RelativePath::new("a file.bin");
+    assert!(
+        repository
+            .create_file(path, &mut "first".as_bytes())
+            .unwrap()
+    );
+    assert!(
+        !repository
+            .create_file(path, &mut "second".as_bytes())
+            .unwrap()
+    );
+    assert_eq!(repository.read_string(path).unwrap(), "first");
+}
+
+#[test]
+fn held_lock_is_not_taken_twice() {
+    let repository = test_repository();
+    let dir = RelativePath::new(GAME);
+    let lock = acquire_lock(&repository, dir, Duration::ZERO, false, &mut |_| {}).unwrap();
+    let error = acquire_lock(&repository, dir, Duration::ZERO, false, &mut |_| {})
+        .err()
+        .unwrap();
+    assert!(error.to_string().contains("is locked by"));
+    drop(lock);
+    assert!(read_holder(&repository).is_none());
+    acquire_lock(&repository, dir, Duration::ZERO, false, &mut |_| {}).unwrap();
+}
+
+#[test]
+fn stale_lock_is_broken() {
+    let repository = test_repository();
+    let stale = other_device(OffsetDateTime::now_utc() - time::Duration::hours(1));
+    write_holder(&repository, &stale);
+    let mut broken = None;
+    let _lock = acquire_lock(
+        &repository,
+        RelativePath::new(GAME),
+        Duration::ZERO,
+        false,
+        &mut |event| {
+            if let LockEvent::Breaking(holder) = event {
+                broken = Some(holder.token);
+            }
+        },
+    )
+    .unwrap();
+    assert_eq!(broken, Some(stale.token));
+    assert_ne!(read_holder(&repository).unwrap().token, stale.token);
+}
+
+#[test]
+fn lock_taken_over_by_another_device_is_left_alone() {
+    let repository = test_repository();
+    let lock = acquire_lock(
+        &repository,
+        RelativePath::new(GAME),
+        Duration::ZERO,
+        false,
+        &mut |_| {},
+    )
+    .unwrap();
+    // Another device broke the lock with --break-lock and took it
+    let other = other_device(OffsetDateTime::now_utc());
+    write_holder(&repository, &other);
+    assert!(lock.refresh().is_err());
+    drop(lock);
+    assert_eq!(read_holder(&repository).unwrap().token, other.token);
+}
+
+#[test]
+fn refresh_moves_the_lock_timestamp() {
+    let repository = test_repository();
+    let lock = acquire_lock(
+        &repository,
+        RelativePath::new(GAME),
+        Duration::ZERO,
+        false,
+        &mut |_| {},
+    )
+    .unwrap();
+    let mut holder = read_holder(&repository).unwrap();
+    holder.timestamp -= time::Duration::minutes(5);
+    write_holder(&repository, &holder);
+    lock.refresh().unwrap();
+    let refreshed = read_holder(&repository).unwrap();
+    assert_eq!(refreshed.token, holder.token);
+    assert!(refreshed.timestamp > holder.timestamp);
+}
@@ -0,0 +1,183 @@
+mod common;
+
+use std::io::Read;
+
+use common::{
+    contract::{assert_file_operations, assert_sync_between_devices},
+    s3::{ACCESS_KEY_ID, BUCKET, S3Server, SECRET_ACCESS_KEY},
+    saved_config,
+};
+use gamesavesync::repository::{
+    AnyRepositoryConfig, Repository, RepositoryExt,
+    s3_repository::{S3Repository, S3RepositoryConfig, open_repository},
+};
+use relative_path::{RelativePath, RelativePathBuf};
+
+fn open(server: &S3Server, bucket: &str, secret: &str) -> anyhow::Result<S3Repository> {
+    open_repository(&S3RepositoryConfig {
+        endpoint: server.endpoint.clone(),
+        bucket: bucket.into(),
+        region: "us-east-1".into(),
+        prefix: "GameSaveSync/".into(),
+        access_key_id: Some(ACCESS_KEY_ID.into()),
+        secret_access_key: Some(secret.into()),
+    })
+}
+
+fn entries(repository: &S3Repository, path: &str) -> Vec<RelativePathBuf> {
+    repository
+        .read_dir(RelativePath::new(path))
+        .unwrap()
+        .collect::<anyhow::Result<_>>()
+        .unwrap()
+}
+
+#[test]
+fn s3_object_operations() {
+    let server = S3Server::start();
+    let repository = open(&server, BUCKET, SECRET_ACCESS_KEY).unwrap();
+    assert_file_operations(&repository);
+    assert!(server.object_keys().is_empty());
+    repository
+        .write_string(RelativePath::new("Some Game/a file.bin"), "save data")
+        .unwrap();
+    assert_eq!(
+        server.object_keys(),
+        vec!["GameSaveSync/Some Game/a file.bin"]
+    );
+    assert!(
+        repository
+            .read_dir(RelativePath::new("Other Game"))
+            .is_err()
+    );
+}
+
+#[test]
+fn s3_lists_every_page() {
+    let server = S3Server::start();
+    let repository = open(&server, BUCKET, SECRET_ACCESS_KEY).unwrap();
+    let names: Vec<String> = (0..8).map(|index| format!("file{index}")).collect();
+    for name in &names {
+        repository
+            .write_string(&RelativePath::new("Game/blobs").join(name), name)
+            .unwrap();
+    }
+    repository
+        .write_string(RelativePath::new("Game/manifest.toml"), "")
+        .unwrap();
+    let listed: Vec<String> = entries(&repository, "Game/blobs")
+        .iter()
+        .map(|entry| entry.to_string())
+        .collect();
+    assert_eq!(listed, names);
+    let mut game_entries = entries(&repository, "Game");
+    game_entries.sort();
+    assert_eq!(
+        game_entries,
+        vec![
+            RelativePathBuf::from("blobs"),
+            RelativePathBuf::from("manifest.toml")
+        ]
+    );
+    repository.remove(RelativePath::new("Game")).unwrap();
+    assert!(server.object_keys().is_empty());
+}
+
+#[test]
+fn s3_uploads_large_files_in_parts() {
+    let server = S3Server::start();
+    let repository = open(&server, BUCKET, SECRET_ACCESS_KEY).unwrap();
+    let content: Vec<u8> = (0..20 * 1024 * 1024)
+        .map(|index| (index % 251) as u8)
+        .collect();
+    let path = RelativePath::new("Game/blobs/large.bin");
+    repository
+        .write_file(path, &mut content.as_slice())
+        .unwrap();
+    assert_eq!(server.pending_uploads(), 0);
+    let mut read = vec![];
+    repository
+        .read_file(path)
+        .unwrap()
+        .read_to_end(&mut read)
+        .unwrap();
+    assert!(read == content);
+}
+
+#[test]
+fn s3_requires_bucket_and_credentials() {
+    let server = S3Server::start();
+    assert!(open(&server, "missing", SECRET_ACCESS_KEY).is_err());
+    let error = open(&server, BUCKET, "not the secret").err().unwrap();
+    assert!(error.to_string().contains("refused access"));
+    let wrong_key = open_repository(&S3RepositoryConfig {
+        endpoint: server.endpoint.clone(),
+        bucket: BUCKET.into(),
+        region: "us-east-1".into(),
+        prefix: String::new(),
+        access_key_id: Some("someone".into()),
+        secret_access_key: Some(SECRET_ACCESS_KEY.into()),
+    });
+    assert!(wrong_key.is_err());
+}
+
+#[test]
+fn s3_secret_is_not_saved() {
+    let saved = saved_config(AnyRepositoryConfig::S3(S3RepositoryConfig {
+        endpoint: "https://s3.example.com".into(),
+        bucket: BUCKET.into(),
+        region: "us-east-1".into(),
+        prefix: String::new(),
+        access_key_id: Some("AKIDEXAMPLE".into()),
+        secret_access_key: Some("wJalrXUtnFEMI".into()),
+    }));
+    assert!(saved.contains("AKIDEXAMPLE"));
+    assert!(!saved.contains("wJalrXUtnFEMI"));
+}
+
+#[test]
+fn s3_sync_between_devices() {
+    let server = S3Server::start();
+    assert_sync_between_devices(|| open(&server, BUCKET, SECRET_ACCESS_KEY).unwrap());
+}
+
+// Runs against a real service, such as a local MinIO started with
+// GAMESAVESYNC_S3_ENDPOINT=http://localhost:9000 GAMESAVESYNC_S3_BUCKET=saves
+// and the usual AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY
+#[test]
+#[ignore]
+fn s3_service_round_trip() {
+    let env = |name: &str| std::env::var(name).unwrap_or_else(|_| panic!("{name} is not set"));
+    let repository = open_repository(&S3RepositoryConfig {
+        endpoint: env("GAMESAVESYNC_S3_ENDPOINT"),
+        bucket: env("GAMESAVESYNC_S3_BUCKET"),
+        region: std::env::var("AWS_REGION").unwrap_or("us-east-1".into()),
+        prefix: format!("gamesavesync-test-{}", uuid::Uuid::new_v4()),
+        access_key_id: None,
+        secret_access_key: None,
+    })
+    .unwrap();
+    let path = RelativePath::new("Some Game/blobs/a file.bin");
+    repository.write_string(path, "save data").unwrap();
+    assert_eq!(repository.read_string(path).unwrap(), "save data");
+    assert_eq!(
+        entries(&repository, "Some Game"),
+        vec![RelativePathBuf::from("blobs")]
+    );
+    let content: Vec<u8> = (0..10 * 1024 * 1024)
+        .map(|index| (index % 251) as u8)
+        .collect();
+    let large = RelativePath::new("Some Game/blobs/large.bin");
+    repository
+        .write_file(large, &mut content.as_slice())
+        .unwrap();
+    let mut read = vec![];
+    repository
+        .read_file(large)
+        .unwrap()
+        .read_to_end(&mut read)
+        .unwrap();
+    assert!(read == content);
+    repository.remove(RelativePath::new("")).unwrap();
+    assert!(!repository.is_dir(RelativePath::new("Some Game")).unwrap());
+}
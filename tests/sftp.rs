@@ -0,0 +1,41 @@
+mod common;
+
+use common::contract::{assert_file_operations, assert_sync_between_devices};
+use gamesavesync::repository::{
+    Repository,
+    sftp_repository::{SftpRepository, SftpRepositoryConfig, open_repository},
+};
+use relative_path::RelativePath;
+
+// Runs against a real server in an empty scratch directory, configured with
+// GAMESAVESYNC_SFTP_HOST, GAMESAVESYNC_SFTP_USER and GAMESAVESYNC_SFTP_PATH, plus
+// GAMESAVESYNC_SFTP_PORT, GAMESAVESYNC_SFTP_KEY_FILE and GAMESAVESYNC_SFTP_KNOWN_HOSTS if needed
+fn open() -> SftpRepository {
+    let env = |name: &str| std::env::var(name).unwrap_or_else(|_| panic!("{name} is not set"));
+    let optional = |name: &str| std::env::var(name).ok();
+    open_repository(&SftpRepositoryConfig {
+        host: env("GAMESAVESYNC_SFTP_HOST"),
+        port: optional("GAMESAVESYNC_SFTP_PORT").map_or(22, |port| port.parse().unwrap()),
+        user: env("GAMESAVESYNC_SFTP_USER"),
+        path: env("GAMESAVESYNC_SFTP_PATH"),
+        key_file: optional("GAMESAVESYNC_SFTP_KEY_FILE").map(Into::into),
+        known_hosts: optional("GAMESAVESYNC_SFTP_KNOWN_HOSTS").map(Into::into),
+    })
+    .unwrap()
+}
+
+#[test]
+#[ignore]
+fn sftp_file_operations() {
+    assert_file_operations(&open());
+}
+
+#[test]
+#[ignore]
+fn sftp_sync_between_devices() {
+    assert_sync_between_devices(open);
+    let repository = open();
+    for entry in repository.read_dir(RelativePath::new("")).unwrap() {
+        repository.remove(&entry.unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,162 @@
+mod common;
+
+use common::{GAME, ScriptedResolver, TestDevice, test_repository};
+use gamesavesync::{
+    games::definition::load_definition,
+    repository::Repository,
+    sync::{
+        callbacks::ConflictResolution,
+        diff::{FileChange, SaveComparison, SaveDiff, diff_save_state},
+        load_save_state,
+        status::{GameStatus, game_status},
+    },
+};
+
+// Two devices that both synced the first save
+fn synced_devices(repository: &impl Repository) -> (TestDevice, TestDevice) {
+    let first = TestDevice::new();
+    let second = TestDevice::new();
+    first.write_save("slot1.sav", "level 1");
+    first.write_save("slot2.sav", "level 1");
+    first
+        .sync(repository, &mut ScriptedResolver::default())
+        .unwrap();
+    second
+        .sync(
+            repository,
+            &mut ScriptedResolver::new(&[ConflictResolution::UseRepository { keep_other: false }]),
+        )
+        .unwrap();
+    (first, second)
+}
+
+fn status(device: &TestDevice, repository: &impl Repository) -> GameStatus {
+    device.activate();
+    game_status(GAME, repository).unwrap().status
+}
+
+fn diffs(device: &TestDevice, repository: &impl Repository) -> Vec<SaveDiff> {
+    device.activate();
+    let state = load_save_state(&load_definition(repository, GAME).unwrap(), repository).unwrap();
+    diff_save_state(
+        &state.local_files,
+        state.synced.as_ref().map(|(files, _)| files),
+        &state.repository.unwrap().0,
+    )
+}
+
+// The changed file names for each comparison, in the order they are shown
+fn changed(diffs: &[SaveDiff]) -> Vec<(SaveComparison, Vec<(String, FileChange)>)> {
+    diffs
+        .iter()
+        .map(|diff| {
+            (
+                diff.comparison,
+                diff.files
+                    .iter()
+                    .map(|file| (file.name.clone(), file.change))
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn unchanged_save_is_in_sync() {
+    let repository = test_repository();
+    let (first, _) = synced_devices(&repository);
+    assert_eq!(status(&first, &repository), GameStatus::InSync);
+    assert_eq!(
+        changed(&diffs(&first, &repository)),
+        vec![
+            (SaveComparison::LocalSinceSync, vec![]),
+            (SaveComparison::RepositorySinceSync, vec![]),
+        ]
+    );
+}
+
+#[test]
+fn local_change_is_ahead() {
+    let repository = test_repository();
+    let (first, _) = synced_devices(&repository);
+    first.write_save("slot1.sav", "level 2");
+    first.write_save("slot3.sav", "level 2");
+    assert_eq!(status(&first, &repository), GameStatus::LocalAhead);
+    assert_eq!(
+        changed(&diffs(&first, &repository)),
+        vec![
+            (
+                SaveComparison::LocalSinceSync,
+                vec![
+                    ("<home>/saves/slot1.sav".into(), FileChange::Modified),
+                    ("<home>/saves/slot3.sav".into(), FileChange::Added),
+                ]
+            ),
+            (SaveComparison::RepositorySinceSync, vec![]),
+        ]
+    );
+}
+
+#[test]
+fn repository_change_is_ahead() {
+    let repository = test_repository();
+    let (first, second) = synced_devices(&repository);
+    second.remove_save("slot2.sav");
+    second
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    assert_eq!(status(&first, &repository), GameStatus::RepositoryAhead);
+    assert_eq!(
+        changed(&diffs(&first, &repository)),
+        vec![
+            (SaveComparison::LocalSinceSync, vec![]),
+            (
+                SaveComparison::RepositorySinceSync,
+                vec![("<home>/saves/slot2.sav".into(), FileChange::Removed)]
+            ),
+        ]
+    );
+}
+
+#[test]
+fn both_changed_files_merge_or_conflict() {
+    let repository = test_repository();
+    let (first, second) = synced_devices(&repository);
+    second.write_save("slot2.sav", "level 2 on second");
+    second
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    first.write_save("slot1.sav", "level 2 on first");
+    assert_eq!(status(&first, &repository), GameStatus::WillMerge);
+    assert_eq!(
+        changed(&diffs(&first, &repository)),
+        vec![
+            (
+                SaveComparison::LocalSinceSync,
+                vec![("<home>/saves/slot1.sav".into(), FileChange::Modified)]
+            ),
+            (
+                SaveComparison::RepositorySinceSync,
+                vec![("<home>/saves/slot2.sav".into(), FileChange::Modified)]
+            ),
+        ]
+    );
+    first.write_save("slot2.sav", "level 3 on first");
+    assert_eq!(status(&first, &repository), GameStatus::Conflict);
+}
+
+#[test]
+fn unsynced_device_compares_with_repository() {
+    let repository = test_repository();
+    synced_devices(&repository);
+    let third = TestDevice::new();
+    assert_eq!(status(&third, &repository), GameStatus::NeverSynced);
+    third.write_save("slot1.sav", "level 1");
+    assert_eq!(
+        changed(&diffs(&third, &repository)),
+        vec![(
+            SaveComparison::LocalToRepository,
+            vec![("<home>/saves/slot2.sav".into(), FileChange::Removed)]
+        )]
+    );
+}
@@ -0,0 +1,824 @@
+mod common;
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use common::{GAME, ScriptedResolver, TestDevice, test_repository};
+use gamesavesync::{
+    games::{
+        ConflictPolicy,
+        blobs::{blob_path, collect_garbage},
+        definition::load_definition,
+        history::{find_branch, list_snapshots, read_snapshot_manifest, remove_snapshot},
+        journal::begin_apply,
+        manifest::{
+            GameSaveStorage, ManifestChange, read_repository_manifest, read_synced_manifest,
+            write_repository_manifest, write_synced_manifest,
+        },
+    },
+    repository::{
+        Repository, RepositoryExt,
+        settings::{RepositorySettings, RetentionPolicy, SETTINGS_FILE, write_settings},
+    },
+    sync::{
+        FileChanges, SyncAction, SyncOptions,
+        callbacks::{ConflictReason, ConflictResolution, SyncEvent},
+        classify_file_changes,
+        files::{get_local_files, hash_file},
+        load_save_state, restore_snapshot,
+    },
+    utils::paths::make_path_safe,
+};
+use relative_path::RelativePath;
+use uuid::Uuid;
+
+const USE_REPOSITORY: ConflictResolution = ConflictResolution::UseRepository { keep_other: false };
+
+fn file_changes(device: &TestDevice, repository: &impl Repository) -> FileChanges {
+    device.activate();
+    let state = load_save_state(&load_definition(repository, GAME).unwrap(), repository).unwrap();
+    classify_file_changes(
+        &state.local_files,
+        &state.synced.unwrap().0,
+        &state.repository.unwrap().0,
+    )
+}
+
+#[test]
+fn first_sync_stores_local_save() {
+    let repository = test_repository();
+    let device = TestDevice::new();
+    device.write_save("slot1.sav", "level 1");
+    let mut resolver = ScriptedResolver::default();
+    let report = device.sync(&repository, &mut resolver).unwrap();
+    assert_eq!(report.action, SyncAction::Stored);
+    let manifest = read_repository_manifest(&repository, GAME)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        manifest
+            .files
+            .values()
+            .map(|files| files.len())
+            .sum::<usize>(),
+        1
+    );
+    assert_eq!(
+        read_synced_manifest(GAME).unwrap().map(|synced| synced.id),
+        Some(manifest.id)
+    );
+    assert!(resolver.conflicts.is_empty());
+}
+
+#[test]
+fn second_sync_without_changes_is_up_to_date() {
+    let repository = test_repository();
+    let device = TestDevice::new();
+    device.write_save("slot1.sav", "level 1");
+    let mut resolver = ScriptedResolver::default();
+    device.sync(&repository, &mut resolver).unwrap();
+    let report = device.sync(&repository, &mut resolver).unwrap();
+    assert_eq!(report.action, SyncAction::UpToDate);
+}
+
+#[test]
+fn first_sync_on_another_device_asks_and_applies() {
+    let repository = test_repository();
+    let first = TestDevice::new();
+    first.write_save("slot1.sav", "level 1");
+    first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    let second = TestDevice::new();
+    let mut resolver = ScriptedResolver::new(&[USE_REPOSITORY]);
+    let report = second.sync(&repository, &mut resolver).unwrap();
+    assert_eq!(report.action, SyncAction::Applied);
+    assert_eq!(resolver.conflicts, vec![ConflictReason::NeverSynced]);
+    assert_eq!(second.read_save("slot1.sav").as_deref(), Some("level 1"));
+}
+
+#[test]
+fn local_ahead_stores_changes() {
+    let repository = test_repository();
+    let device = TestDevice::new();
+    device.write_save("slot1.sav", "level 1");
+    let mut resolver = ScriptedResolver::default();
+    device.sync(&repository, &mut resolver).unwrap();
+    let before = read_repository_manifest(&repository, GAME)
+        .unwrap()
+        .unwrap();
+    device.write_save("slot1.sav", "level 2 reached");
+    let report = device.sync(&repository, &mut resolver).unwrap();
+    assert_eq!(report.action, SyncAction::Stored);
+    let after = read_repository_manifest(&repository, GAME)
+        .unwrap()
+        .unwrap();
+    assert_eq!(after.parent, Some(before.id));
+    assert!(resolver.conflicts.is_empty());
+}
+
+#[test]
+fn repository_ahead_applies_changes() {
+    let repository = test_repository();
+    let first = TestDevice::new();
+    let second = TestDevice::new();
+    first.write_save("slot1.sav", "level 1");
+    first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    second
+        .sync(&repository, &mut ScriptedResolver::new(&[USE_REPOSITORY]))
+        .unwrap();
+    first.write_save("slot1.sav", "level 2 reached");
+    first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    let mut resolver = ScriptedResolver::default();
+    let report = second.sync(&repository, &mut resolver).unwrap();
+    assert_eq!(report.action, SyncAction::Applied);
+    assert_eq!(
+        second.read_save("slot1.sav").as_deref(),
+        Some("level 2 reached")
+    );
+    assert!(resolver.conflicts.is_empty());
+}
+
+#[test]
+fn both_changed_different_files_merges() {
+    let repository = test_repository();
+    let first = TestDevice::new();
+    let second = TestDevice::new();
+    first.write_save("slot1.sav", "level 1");
+    first.write_save("slot2.sav", "level 1");
+    first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    second
+        .sync(&repository, &mut ScriptedResolver::new(&[USE_REPOSITORY]))
+        .unwrap();
+    first.write_save("slot1.sav", "level 2 on first");
+    first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    second.write_save("slot2.sav", "level 3 on second");
+    let changes = file_changes(&second, &repository);
+    assert_eq!(changes.local, vec![second.save_path("slot2.sav")]);
+    assert_eq!(changes.repository, vec![second.save_path("slot1.sav")]);
+    assert!(changes.conflicts.is_empty());
+    let mut resolver = ScriptedResolver::default();
+    let report = second.sync(&repository, &mut resolver).unwrap();
+    assert_eq!(report.action, SyncAction::Merged);
+    assert!(resolver.conflicts.is_empty());
+    assert_eq!(
+        second.read_save("slot1.sav").as_deref(),
+        Some("level 2 on first")
+    );
+    first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    assert_eq!(
+        first.read_save("slot2.sav").as_deref(),
+        Some("level 3 on second")
+    );
+}
+
+#[test]
+fn failed_merge_keeps_applied_files() {
+    let repository = test_repository();
+    let first = TestDevice::new();
+    let second = TestDevice::new();
+    first.write_save("slot1.sav", "level 1");
+    first.write_save("slot2.sav", "level 1");
+    first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    second
+        .sync(&repository, &mut ScriptedResolver::new(&[USE_REPOSITORY]))
+        .unwrap();
+    first.write_save("slot1.sav", "level 2 on first");
+    first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    second.write_save("slot2.sav", "level 3 on second");
+    // The settings are read once the merged save is stored, so the sync fails after that
+    let settings = repository
+        .read_string(RelativePath::new(SETTINGS_FILE))
+        .unwrap();
+    repository
+        .write_string(RelativePath::new(SETTINGS_FILE), "retention = 1")
+        .unwrap();
+    assert!(
+        second
+            .sync(&repository, &mut ScriptedResolver::default())
+            .is_err()
+    );
+    repository
+        .write_string(RelativePath::new(SETTINGS_FILE), &settings)
+        .unwrap();
+    let report = second
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    assert!(!report.rolled_back);
+    assert_eq!(report.action, SyncAction::UpToDate);
+    assert_eq!(
+        second.read_save("slot1.sav").as_deref(),
+        Some("level 2 on first")
+    );
+    first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    assert_eq!(
+        first.read_save("slot1.sav").as_deref(),
+        Some("level 2 on first")
+    );
+    assert_eq!(
+        first.read_save("slot2.sav").as_deref(),
+        Some("level 3 on second")
+    );
+}
+
+#[test]
+fn interrupted_apply_is_rolled_back() {
+    let repository = test_repository();
+    let first = TestDevice::new();
+    let second = TestDevice::new();
+    first.write_save("slot1.sav", "level 1");
+    first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    second
+        .sync(&repository, &mut ScriptedResolver::new(&[USE_REPOSITORY]))
+        .unwrap();
+    first.write_save("slot1.sav", "level 2");
+    first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    // Left behind the way a sync stopped halfway through copying files would leave it
+    second.activate();
+    let paths = [second.save_path("slot1.sav"), second.save_path("slot2.sav")];
+    begin_apply(GAME, paths.iter().map(PathBuf::as_path)).unwrap();
+    second.write_save("slot1.sav", "partly written");
+    second.write_save("slot2.sav", "partly written");
+    let report = second
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    assert!(report.rolled_back);
+    assert_eq!(report.action, SyncAction::Applied);
+    assert_eq!(second.read_save("slot1.sav").as_deref(), Some("level 2"));
+    assert_eq!(second.read_save("slot2.sav"), None);
+    let report = second
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    assert!(!report.rolled_back);
+    assert_eq!(report.action, SyncAction::UpToDate);
+}
+
+#[test]
+fn both_changed_same_file_asks_resolver() {
+    let repository = test_repository();
+    let first = TestDevice::new();
+    let second = TestDevice::new();
+    first.write_save("slot1.sav", "level 1");
+    first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    second
+        .sync(&repository, &mut ScriptedResolver::new(&[USE_REPOSITORY]))
+        .unwrap();
+    first.write_save("slot1.sav", "level 2 on first");
+    first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    second.write_save("slot1.sav", "level 3 on second");
+    let changes = file_changes(&second, &repository);
+    assert_eq!(changes.conflicts, vec![second.save_path("slot1.sav")]);
+    assert!(changes.local.is_empty() && changes.repository.is_empty());
+
+    let mut resolver = ScriptedResolver::new(&[ConflictResolution::DoNothing]);
+    let report = second.sync(&repository, &mut resolver).unwrap();
+    assert_eq!(report.action, SyncAction::Skipped);
+    assert!(matches!(
+        resolver.conflicts.as_slice(),
+        [ConflictReason::BothChanged { files, .. }] if files == &["slot1.sav"]
+    ));
+    assert_eq!(
+        second.read_save("slot1.sav").as_deref(),
+        Some("level 3 on second")
+    );
+
+    let mut resolver = ScriptedResolver::new(&[ConflictResolution::UseLocal { keep_other: true }]);
+    let report = second.sync(&repository, &mut resolver).unwrap();
+    assert_eq!(report.action, SyncAction::Stored);
+    assert_eq!(report.branches.len(), 1);
+    first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    assert_eq!(
+        first.read_save("slot1.sav").as_deref(),
+        Some("level 3 on second")
+    );
+}
+
+// Both devices change the same file, the first one syncing its change first
+fn conflicting_devices(repository: &impl Repository) -> (TestDevice, TestDevice) {
+    let first = TestDevice::new();
+    let second = TestDevice::new();
+    first.write_save("slot1.sav", "level 1");
+    first
+        .sync(repository, &mut ScriptedResolver::default())
+        .unwrap();
+    second
+        .sync(repository, &mut ScriptedResolver::new(&[USE_REPOSITORY]))
+        .unwrap();
+    first.write_save("slot1.sav", "level 2 on first");
+    first
+        .sync(repository, &mut ScriptedResolver::default())
+        .unwrap();
+    second.write_save("slot1.sav", "level 2 on second");
+    (first, second)
+}
+
+fn set_modified(device: &TestDevice, modified: SystemTime) {
+    std::fs::File::options()
+        .write(true)
+        .open(device.save_path("slot1.sav"))
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+}
+
+fn assert_no_branches(repository: &impl Repository) {
+    assert!(
+        list_snapshots(repository, GAME)
+            .unwrap()
+            .iter()
+            .all(|snapshot| snapshot.branch.is_none())
+    );
+}
+
+#[test]
+fn skip_policy_leaves_both_saves() {
+    let repository = test_repository();
+    let (first, second) = conflicting_devices(&repository);
+    let report = second
+        .sync_with_policy(&repository, ConflictPolicy::Skip)
+        .unwrap();
+    assert_eq!(report.action, SyncAction::Skipped);
+    assert_eq!(
+        second.read_save("slot1.sav").as_deref(),
+        Some("level 2 on second")
+    );
+    first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    assert_eq!(
+        first.read_save("slot1.sav").as_deref(),
+        Some("level 2 on first")
+    );
+}
+
+#[test]
+fn prefer_local_policy_stores_without_branch() {
+    let repository = test_repository();
+    let (first, second) = conflicting_devices(&repository);
+    let report = second
+        .sync_with_policy(&repository, ConflictPolicy::PreferLocal)
+        .unwrap();
+    assert_eq!(report.action, SyncAction::Stored);
+    assert!(report.branches.is_empty());
+    assert_no_branches(&repository);
+    first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    assert_eq!(
+        first.read_save("slot1.sav").as_deref(),
+        Some("level 2 on second")
+    );
+}
+
+#[test]
+fn prefer_repository_policy_applies_without_branch() {
+    let repository = test_repository();
+    let (_, second) = conflicting_devices(&repository);
+    let report = second
+        .sync_with_policy(&repository, ConflictPolicy::PreferRepository)
+        .unwrap();
+    assert_eq!(report.action, SyncAction::Applied);
+    assert!(report.branches.is_empty());
+    assert_no_branches(&repository);
+    assert_eq!(
+        second.read_save("slot1.sav").as_deref(),
+        Some("level 2 on first")
+    );
+}
+
+#[test]
+fn prefer_newest_policy_uses_newer_local_save() {
+    let repository = test_repository();
+    let (_, second) = conflicting_devices(&repository);
+    set_modified(&second, SystemTime::now() + Duration::from_secs(60));
+    let report = second
+        .sync_with_policy(&repository, ConflictPolicy::PreferNewest)
+        .unwrap();
+    assert_eq!(report.action, SyncAction::Stored);
+    assert!(report.branches.is_empty());
+    assert_no_branches(&repository);
+}
+
+#[test]
+fn prefer_newest_policy_uses_newer_repository_save() {
+    let repository = test_repository();
+    let (_, second) = conflicting_devices(&repository);
+    set_modified(&second, SystemTime::UNIX_EPOCH);
+    let report = second
+        .sync_with_policy(&repository, ConflictPolicy::PreferNewest)
+        .unwrap();
+    assert_eq!(report.action, SyncAction::Applied);
+    assert!(report.branches.is_empty());
+    assert_no_branches(&repository);
+    assert_eq!(
+        second.read_save("slot1.sav").as_deref(),
+        Some("level 2 on first")
+    );
+}
+
+#[test]
+fn fail_policy_stops_the_sync() {
+    let repository = test_repository();
+    let (_, second) = conflicting_devices(&repository);
+    assert!(
+        second
+            .sync_with_policy(&repository, ConflictPolicy::Fail)
+            .is_err()
+    );
+    assert_eq!(
+        second.read_save("slot1.sav").as_deref(),
+        Some("level 2 on second")
+    );
+}
+
+#[test]
+fn conflict_branches_get_distinct_names() {
+    let repository = test_repository();
+    let first = TestDevice::new();
+    let second = TestDevice::new();
+    first.write_save("slot1.sav", "level 1");
+    first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    second
+        .sync(&repository, &mut ScriptedResolver::new(&[USE_REPOSITORY]))
+        .unwrap();
+    let keep_both = ConflictResolution::UseLocal { keep_other: true };
+    first.write_save("slot1.sav", "level 2 on first");
+    first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    second.write_save("slot1.sav", "level 2 on second");
+    let report = second
+        .sync(&repository, &mut ScriptedResolver::new(&[keep_both]))
+        .unwrap();
+    first.write_save("slot1.sav", "level 3 on first");
+    let other_report = first
+        .sync(&repository, &mut ScriptedResolver::new(&[keep_both]))
+        .unwrap();
+    let [branch] = report.branches.as_slice() else {
+        panic!("expected one branch, got {:?}", report.branches);
+    };
+    let [other_branch] = other_report.branches.as_slice() else {
+        panic!("expected one branch, got {:?}", other_report.branches);
+    };
+    assert_ne!(branch, other_branch);
+
+    let snapshot = find_branch(&repository, GAME, branch).unwrap().unwrap();
+    let snapshots = list_snapshots(&repository, GAME).unwrap().len();
+    remove_snapshot(&repository, GAME, snapshot.id).unwrap();
+    assert!(find_branch(&repository, GAME, branch).unwrap().is_none());
+    assert!(
+        find_branch(&repository, GAME, other_branch)
+            .unwrap()
+            .is_some()
+    );
+    assert_eq!(
+        list_snapshots(&repository, GAME).unwrap().len(),
+        snapshots - 1
+    );
+}
+
+#[test]
+fn deleted_files_are_removed_on_other_devices() {
+    let repository = test_repository();
+    let first = TestDevice::new();
+    let second = TestDevice::new();
+    first.write_save("slot1.sav", "level 1");
+    first.write_save("slot2.sav", "level 1");
+    first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    second
+        .sync(&repository, &mut ScriptedResolver::new(&[USE_REPOSITORY]))
+        .unwrap();
+    assert!(second.save_path("slot2.sav").is_file());
+    first.remove_save("slot2.sav");
+    let report = first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    assert_eq!(report.action, SyncAction::Stored);
+    let report = second
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    assert_eq!(report.action, SyncAction::Applied);
+    assert!(!second.save_path("slot2.sav").exists());
+    assert_eq!(second.read_save("slot1.sav").as_deref(), Some("level 1"));
+}
+
+#[test]
+fn missing_repository_manifest_stores_local_save() {
+    let repository = test_repository();
+    let device = TestDevice::new();
+    device.write_save("slot1.sav", "level 1");
+    let mut resolver = ScriptedResolver::default();
+    device.sync(&repository, &mut resolver).unwrap();
+    repository
+        .remove(&RelativePath::new(GAME).join("manifest.toml"))
+        .unwrap();
+    assert!(
+        read_repository_manifest(&repository, GAME)
+            .unwrap()
+            .is_none()
+    );
+    let report = device.sync(&repository, &mut resolver).unwrap();
+    assert_eq!(report.action, SyncAction::Stored);
+    assert!(
+        read_repository_manifest(&repository, GAME)
+            .unwrap()
+            .is_some()
+    );
+    assert!(resolver.conflicts.is_empty());
+}
+
+fn restore(device: &TestDevice, repository: &impl Repository, id: Uuid, make_current: bool) {
+    device.activate();
+    let snapshot = read_snapshot_manifest(repository, GAME, id)
+        .unwrap()
+        .unwrap();
+    let local_files = get_local_files(&snapshot.definition, None).unwrap();
+    restore_snapshot(
+        &snapshot,
+        &local_files,
+        make_current,
+        repository,
+        &SyncOptions::default(),
+        &mut |_: &SyncEvent| {},
+    )
+    .unwrap();
+}
+
+#[test]
+fn restored_save_is_stored_on_next_sync() {
+    let repository = test_repository();
+    let first = TestDevice::new();
+    let second = TestDevice::new();
+    first.write_save("slot1.sav", "level 1");
+    first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    let old = read_repository_manifest(&repository, GAME)
+        .unwrap()
+        .unwrap();
+    first.write_save("slot1.sav", "level 2");
+    first.write_save("slot2.sav", "level 2");
+    first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+
+    restore(&first, &repository, old.id, false);
+    assert_eq!(first.read_save("slot1.sav").as_deref(), Some("level 1"));
+    assert_eq!(first.read_save("slot2.sav"), None);
+    let mut resolver = ScriptedResolver::default();
+    let report = first.sync(&repository, &mut resolver).unwrap();
+    assert_eq!(report.action, SyncAction::Stored);
+    assert!(resolver.conflicts.is_empty());
+    second
+        .sync(&repository, &mut ScriptedResolver::new(&[USE_REPOSITORY]))
+        .unwrap();
+    assert_eq!(second.read_save("slot1.sav").as_deref(), Some("level 1"));
+    assert_eq!(second.read_save("slot2.sav"), None);
+}
+
+#[test]
+fn restore_can_make_the_snapshot_current() {
+    let repository = test_repository();
+    let first = TestDevice::new();
+    let second = TestDevice::new();
+    first.write_save("slot1.sav", "level 1");
+    first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    let old = read_repository_manifest(&repository, GAME)
+        .unwrap()
+        .unwrap();
+    first.write_save("slot1.sav", "level 2");
+    first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+
+    restore(&first, &repository, old.id, true);
+    assert_eq!(first.read_save("slot1.sav").as_deref(), Some("level 1"));
+    let head = read_repository_manifest(&repository, GAME)
+        .unwrap()
+        .unwrap();
+    assert_eq!(head.id, old.id);
+    assert_eq!(head.branch, None);
+    assert!(head.timestamp > old.timestamp);
+    let report = first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    assert_eq!(report.action, SyncAction::UpToDate);
+    second
+        .sync(&repository, &mut ScriptedResolver::new(&[USE_REPOSITORY]))
+        .unwrap();
+    assert_eq!(second.read_save("slot1.sav").as_deref(), Some("level 1"));
+}
+
+#[test]
+fn manifests_from_before_hashing_still_sync() {
+    let repository = test_repository();
+    let first = TestDevice::new();
+    let second = TestDevice::new();
+    first.write_save("slot1.sav", "level 1");
+    first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    // Rewritten the way older versions stored it, with a directory per snapshot and no hashes
+    let mut legacy = read_repository_manifest(&repository, GAME)
+        .unwrap()
+        .unwrap();
+    legacy.storage = GameSaveStorage::Directory;
+    for (path, files) in legacy.files.iter_mut() {
+        for (file, metadata) in files.iter_mut() {
+            metadata.hash = None;
+            let stored = RelativePath::new(GAME)
+                .join(legacy.id.to_string())
+                .join(make_path_safe(path))
+                .join(file);
+            repository.write_string(&stored, "level 1").unwrap();
+        }
+    }
+    repository
+        .remove(&RelativePath::new(GAME).join("blobs"))
+        .unwrap();
+    write_repository_manifest(&legacy, ManifestChange::Stored, &repository).unwrap();
+    first.activate();
+    write_synced_manifest(&legacy).unwrap();
+    let serialized = repository
+        .read_string(&RelativePath::new(GAME).join("manifest.toml"))
+        .unwrap();
+    assert!(!serialized.contains("hash"));
+
+    let report = first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    assert_eq!(report.action, SyncAction::UpToDate);
+    let report = second
+        .sync(&repository, &mut ScriptedResolver::new(&[USE_REPOSITORY]))
+        .unwrap();
+    assert_eq!(report.action, SyncAction::Applied);
+    assert_eq!(second.read_save("slot1.sav").as_deref(), Some("level 1"));
+
+    first.write_save("slot1.sav", "level 2");
+    let report = first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    assert_eq!(report.action, SyncAction::Stored);
+    let report = second
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    assert_eq!(report.action, SyncAction::Applied);
+    assert_eq!(second.read_save("slot1.sav").as_deref(), Some("level 2"));
+}
+
+#[test]
+fn cached_hashes_are_only_used_for_unchanged_files() {
+    let repository = test_repository();
+    let device = TestDevice::new();
+    device.write_save("slot1.sav", "level 1");
+    device.activate();
+    let definition = load_definition(&repository, GAME).unwrap();
+    let path = device.save_path("slot1.sav");
+    let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+    let mut cached = get_local_files(&definition, None).unwrap();
+    cached.get_mut(&path).unwrap().2.hash = Some("cached".into());
+    let hash = || {
+        get_local_files(&definition, Some(&cached)).unwrap()[&path]
+            .2
+            .hash
+            .clone()
+            .unwrap()
+    };
+    assert_eq!(hash(), "cached");
+
+    // Same size, but modified since the hash was cached
+    std::fs::write(&path, "level 2").unwrap();
+    set_modified(&device, modified + Duration::from_secs(1));
+    assert_eq!(hash(), hash_file(&path).unwrap());
+
+    // Same modified time, but a different size
+    std::fs::write(&path, "level 10").unwrap();
+    set_modified(&device, modified);
+    assert_eq!(hash(), hash_file(&path).unwrap());
+}
+
+fn stored_hash(repository: &impl Repository, file: &str) -> String {
+    let manifest = read_repository_manifest(repository, GAME).unwrap().unwrap();
+    manifest.files["<home>/saves"][RelativePath::new(file)]
+        .hash
+        .clone()
+        .unwrap()
+}
+
+fn stored_blobs(repository: &impl Repository) -> HashSet<String> {
+    repository
+        .read_dir(&RelativePath::new(GAME).join("blobs"))
+        .unwrap()
+        .map(|path| path.unwrap().file_name().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn identical_files_share_one_blob() {
+    let repository = test_repository();
+    let device = TestDevice::new();
+    device.write_save("slot1.sav", "same save");
+    device.write_save("slot2.sav", "same save");
+    device
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    let hash = stored_hash(&repository, "slot1.sav");
+    assert_eq!(stored_hash(&repository, "slot2.sav"), hash);
+    assert_eq!(stored_blobs(&repository), HashSet::from([hash.clone()]));
+    assert_eq!(
+        repository.read_string(&blob_path(GAME, &hash)).unwrap(),
+        "same save"
+    );
+}
+
+#[test]
+fn garbage_collection_keeps_retained_and_branch_blobs() {
+    let repository = test_repository();
+    write_settings(
+        &repository,
+        &RepositorySettings {
+            retention: RetentionPolicy {
+                keep_last: Some(2),
+                keep_daily: None,
+                keep_weekly: None,
+            },
+        },
+    )
+    .unwrap();
+    let first = TestDevice::new();
+    let second = TestDevice::new();
+    first.write_save("slot1.sav", "level 1");
+    first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    let level_1 = stored_hash(&repository, "slot1.sav");
+    second
+        .sync(&repository, &mut ScriptedResolver::new(&[USE_REPOSITORY]))
+        .unwrap();
+    first.write_save("slot1.sav", "level 2 on first");
+    first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    let branched = stored_hash(&repository, "slot1.sav");
+    second.write_save("slot1.sav", "level 2 on second");
+    let keep_both = ConflictResolution::UseLocal { keep_other: true };
+    second
+        .sync(&repository, &mut ScriptedResolver::new(&[keep_both]))
+        .unwrap();
+    let retained = stored_hash(&repository, "slot1.sav");
+    first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    first.write_save("slot1.sav", "level 3");
+    first
+        .sync(&repository, &mut ScriptedResolver::default())
+        .unwrap();
+    let current = stored_hash(&repository, "slot1.sav");
+
+    // The first save fell out of retention, the branch is kept however old it is
+    assert_eq!(
+        stored_blobs(&repository),
+        HashSet::from([branched, retained, current])
+    );
+    assert!(!stored_blobs(&repository).contains(&level_1));
+    repository
+        .write_string(&blob_path(GAME, "unreferenced"), "lost save")
+        .unwrap();
+    assert_eq!(
+        collect_garbage(&repository, GAME).unwrap(),
+        vec!["unreferenced".to_string()]
+    );
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use common::{
+    contract::{assert_file_operations, assert_sync_between_devices},
+    saved_config,
+    webdav::WebDavServer,
+};
+use gamesavesync::repository::{
+    AnyRepositoryConfig,
+    webdav_repository::{WebDavRepository, WebDavRepositoryConfig, open_repository},
+};
+
+fn open(
+    server: &WebDavServer,
+    credentials: Option<(&str, &str)>,
+) -> anyhow::Result<WebDavRepository> {
+    open_repository(&WebDavRepositoryConfig {
+        url: server.url.clone(),
+        username: credentials.map(|(username, _)| username.into()),
+        password: credentials.map(|(_, password)| password.into()),
+        ca_certificate: None,
+        accept_invalid_certificates: false,
+    })
+}
+
+#[test]
+fn webdav_file_operations() {
+    let server = WebDavServer::start(None);
+    assert_file_operations(&open(&server, None).unwrap());
+}
+
+#[test]
+fn webdav_requires_credentials() {
+    let server = WebDavServer::start(Some(("player", "secret")));
+    assert!(open(&server, None).is_err());
+    assert!(open(&server, Some(("player", "wrong"))).is_err());
+    assert!(open(&server, Some(("player", "secret"))).is_ok());
+}
+
+#[test]
+fn webdav_password_is_not_saved() {
+    let saved = saved_config(AnyRepositoryConfig::WebDav(WebDavRepositoryConfig {
+        url: "https://saves.example.com/dav".into(),
+        username: Some("player".into()),
+        password: Some("secret".into()),
+        ca_certificate: None,
+        accept_invalid_certificates: false,
+    }));
+    assert!(saved.contains("player"));
+    assert!(!saved.contains("secret"));
+}
+
+#[test]
+fn webdav_sync_between_devices() {
+    let server = WebDavServer::start(None);
+    assert_sync_between_devices(|| open(&server, None).unwrap());
+}